@@ -4,10 +4,8 @@ use std::fs;
 use std::io;
 use std::path::PathBuf;
 
-use bincode;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use serde_json;
 
 #[cfg(any(feature = "seccomp", feature = "cap-ng"))]
 use bindgen;
@@ -15,6 +13,8 @@ use bindgen;
 const SECCOMP_HEADER: &str = "/usr/include/seccomp.h";
 const CAPNG_HEADER: &str = "/usr/include/cap-ng.h";
 const COMPILER_CONFIGS: &str = "src/compiler/backends/";
+#[cfg(feature = "seccomp")]
+const SECCOMP_PROFILES: &str = "src/executor/seccomp/profiles/";
 
 lazy_static! {
     static ref OUT_DIR: PathBuf = {
@@ -31,6 +31,8 @@ fn main() -> io::Result<()> {
     watch_changes()?;
     generate_compiler_configs()?;
     #[cfg(feature = "seccomp")]
+    generate_seccomp_profiles()?;
+    #[cfg(feature = "seccomp")]
     generate_libseccomp_binding()?;
     #[cfg(feature = "cap-ng")]
     generate_libcap_ng_binding()?;
@@ -38,13 +40,20 @@ fn main() -> io::Result<()> {
 }
 
 fn watch_changes() -> io::Result<()> {
-    println!("cargo:rerun-if-changed={}", "Cargo.toml");
-    fs::read_dir(&ROOT_DIR.join(COMPILER_CONFIGS))?.for_each(|backend| {
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    fs::read_dir(ROOT_DIR.join(COMPILER_CONFIGS))?.for_each(|backend| {
         println!(
             "cargo:rerun-if-changed={}",
             backend.unwrap().path().to_str().unwrap()
         );
     });
+    #[cfg(feature = "seccomp")]
+    fs::read_dir(ROOT_DIR.join(SECCOMP_PROFILES))?.for_each(|profile| {
+        println!(
+            "cargo:rerun-if-changed={}",
+            profile.unwrap().path().to_str().unwrap()
+        );
+    });
     println!("cargo:rerun-if-changed={}", SECCOMP_HEADER);
     println!("cargo:rerun-if-changed={}", CAPNG_HEADER);
     Ok(())
@@ -59,27 +68,21 @@ fn generate_compiler_configs() -> io::Result<()> {
         timeout: u64,
     }
 
-    let languages: HashMap<String, CompilerConfig> =
-        fs::read_dir(&ROOT_DIR.join(COMPILER_CONFIGS))?
-            .map(|entry| entry.unwrap())
-            .filter(|entry| entry.file_name().to_str().unwrap().ends_with(".json"))
-            .map(|entry| {
-                (
-                    entry.file_name(),
-                    fs::read_to_string(&entry.path()).unwrap(),
-                )
-            })
-            .map(|(filename, config)| {
-                let language = filename
-                    .to_str()
-                    .unwrap()
-                    .trim_end_matches(".json")
-                    .to_owned();
-                let config = serde_json::from_str(&config)
-                    .expect(&format!("Configuration file {} is unavailable", &language));
-                (language, config)
-            })
-            .collect();
+    let languages: HashMap<String, CompilerConfig> = fs::read_dir(ROOT_DIR.join(COMPILER_CONFIGS))?
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_name().to_str().unwrap().ends_with(".json"))
+        .map(|entry| (entry.file_name(), fs::read_to_string(entry.path()).unwrap()))
+        .map(|(filename, config)| {
+            let language = filename
+                .to_str()
+                .unwrap()
+                .trim_end_matches(".json")
+                .to_owned();
+            let config = serde_json::from_str(&config)
+                .unwrap_or_else(|_| panic!("Configuration file {} is unavailable", &language));
+            (language, config)
+        })
+        .collect();
 
     fs::write(
         OUT_DIR.join("languages"),
@@ -89,6 +92,40 @@ fn generate_compiler_configs() -> io::Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "seccomp")]
+fn generate_seccomp_profiles() -> io::Result<()> {
+    #[derive(Serialize, Deserialize)]
+    struct Profile {
+        description: String,
+        syscalls: Vec<String>,
+        #[serde(default)]
+        denied: Vec<String>,
+    }
+
+    let profiles: HashMap<String, Profile> = fs::read_dir(ROOT_DIR.join(SECCOMP_PROFILES))?
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_name().to_str().unwrap().ends_with(".json"))
+        .map(|entry| (entry.file_name(), fs::read_to_string(entry.path()).unwrap()))
+        .map(|(filename, profile)| {
+            let name = filename
+                .to_str()
+                .unwrap()
+                .trim_end_matches(".json")
+                .to_owned();
+            let profile = serde_json::from_str(&profile)
+                .unwrap_or_else(|_| panic!("Seccomp profile {} is unavailable", &name));
+            (name, profile)
+        })
+        .collect();
+
+    fs::write(
+        OUT_DIR.join("seccomp_profiles"),
+        bincode::serialize(&profiles).unwrap(),
+    )?;
+
+    Ok(())
+}
+
 #[cfg(feature = "seccomp")]
 fn generate_libseccomp_binding() -> io::Result<()> {
     println!("cargo:rustc-link-lib=dylib=seccomp");
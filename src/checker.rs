@@ -1,13 +1,25 @@
-//! Utils to compare two files line by line.
+//! Utils to compare two files line by line or token by token.
+use std::fmt::{self, Display};
 use std::fs;
 use std::io::{self, BufRead};
 use std::path;
 use std::process;
 
+use crate::error::{Error, Result};
+
 /// A configurable content comparer.
 pub struct Checker<'a> {
     ignore_white_space_at_line_end: bool,
     ignore_empty_line_at_file_end: bool,
+    /// Compare two files as streams of white space separated tokens.
+    ///
+    /// The `ignore_*` options will be ignored in this mode.
+    compare_by_token: bool,
+    /// Absolute and relative error allowed between two numeric tokens.
+    ///
+    /// If it is set, the checker compares two files token by token
+    /// and the `ignore_*` options will be ignored.
+    float_tolerance: Option<(f64, f64)>,
     /// If the checker use an extern program,
     /// the checker provide the filesystem path of input file, output file
     /// and answer file as three arguments to the extern program
@@ -32,47 +44,78 @@ impl<'a> Checker<'a> {
         self
     }
 
+    /// Set if the checker compare two files token by token.
+    ///
+    /// Tokens are separated by any white space including tabs and
+    /// carriage returns, so the layout of lines is not considered.
+    pub fn compare_by_token(mut self, flag: bool) -> Checker<'a> {
+        self.compare_by_token = flag;
+        self
+    }
+
+    /// Compare two files token by token and allow an absolute error
+    /// or a relative error between two numeric tokens.
+    ///
+    /// Other tokens must be exactly the same.
+    pub fn float_tolerance(mut self, absolute: f64, relative: f64) -> Checker<'a> {
+        self.float_tolerance = Some((absolute, relative));
+        self
+    }
+
     /// Use an extern program to check the output.
     pub fn extern_program(mut self, program: &'a path::Path) -> Checker<'a> {
         self.extern_program = Some(program);
         self
     }
 
-    /// Compare output file and answer file line by line.
+    /// Compare output file and answer file.
     ///
     /// Return `true` if there is no difference between two files.
     pub fn compare_files(
         &self,
         output_file: &path::Path,
         answer_file: &path::Path,
-    ) -> io::Result<bool> {
-        let mut output_buf = {
-            let file = fs::File::open(output_file)?;
-            io::BufReader::new(file)
-        };
-
-        let mut answer_buf = {
-            let file = fs::File::open(answer_file)?;
-            io::BufReader::new(file)
-        };
-
-        Ok(!self.buf_diff(&mut output_buf, &mut answer_buf)?)
+    ) -> Result<bool> {
+        let (mut output_buf, mut answer_buf) = open_files(output_file, answer_file)?;
+        Ok(self.buf_diff(&mut output_buf, &mut answer_buf)?.is_none())
     }
 
     /// Compare two strings.
     ///
     /// Return `true` if there is no difference between two strings.
-    pub fn compare_str(&self, output: &str, answer: &str) -> io::Result<bool> {
+    pub fn compare_str(&self, output: &str, answer: &str) -> Result<bool> {
         self.compare_bytes(output.as_bytes(), answer.as_bytes())
     }
 
     /// Compare two bytes arrays.
     ///
     /// Return `true` if there is no difference between two arrays.
-    pub fn compare_bytes(&self, output: &[u8], answer: &[u8]) -> io::Result<bool> {
+    pub fn compare_bytes(&self, output: &[u8], answer: &[u8]) -> Result<bool> {
         let mut output_buf = io::BufReader::new(output);
         let mut answer_buf = io::BufReader::new(answer);
-        Ok(!self.buf_diff(&mut output_buf, &mut answer_buf)?)
+        Ok(self.buf_diff(&mut output_buf, &mut answer_buf)?.is_none())
+    }
+
+    /// Check output file with answer file.
+    ///
+    /// Unlike `compare_files`, the result tells whether the difference is
+    /// only a presentation error and where the first difference is.
+    pub fn check_files(
+        &self,
+        output_file: &path::Path,
+        answer_file: &path::Path,
+    ) -> Result<CheckResult> {
+        self.check_with(|| open_files(output_file, answer_file))
+    }
+
+    /// Check output string with answer string.
+    pub fn check_str(&self, output: &str, answer: &str) -> Result<CheckResult> {
+        self.check_bytes(output.as_bytes(), answer.as_bytes())
+    }
+
+    /// Check output bytes array with answer bytes array.
+    pub fn check_bytes(&self, output: &[u8], answer: &[u8]) -> Result<CheckResult> {
+        self.check_with(|| Ok((io::BufReader::new(output), io::BufReader::new(answer))))
     }
 
     /// Check output by provided extern program.
@@ -80,38 +123,90 @@ impl<'a> Checker<'a> {
     /// The result is completely depended on the extern program.
     /// So the `ignore_*` options of the checker will be ignored.
     ///
-    /// Return `true` if extern program exit successfully.
+    /// The extern program should follow the conventions of testlib,
+    /// see `CheckResult::from_testlib` for details.
     pub fn check_use_extern_program(
         &self,
         input_file: &path::Path,
         output_file: &path::Path,
         answer_file: &path::Path,
-    ) -> io::Result<bool> {
+    ) -> Result<CheckResult> {
         if let Some(spj) = self.extern_program {
-            let check_result = process::Command::new(spj)
+            let output = process::Command::new(spj)
                 .arg(input_file)
-                .arg(answer_file)
                 .arg(output_file)
-                .status()?
-                .success();
-            Ok(check_result)
+                .arg(answer_file)
+                .stdin(process::Stdio::null())
+                .stdout(process::Stdio::null())
+                .stderr(process::Stdio::piped())
+                .output()?;
+            Ok(CheckResult::from_testlib(output.status, &output.stderr))
         } else {
-            panic!("Checker's extern program has not been set")
+            Err(Error::Checker(String::from(
+                "Checker's extern program has not been set",
+            )))
         }
     }
 
+    /// Check two buffers opened by `open`.
+    ///
+    /// If the buffers are different line by line but equal token by token,
+    /// the output is a presentation error.
+    /// So the buffers may be opened twice.
+    fn check_with<T, U, F>(&self, open: F) -> Result<CheckResult>
+    where
+        T: BufRead,
+        U: BufRead,
+        F: Fn() -> io::Result<(T, U)>,
+    {
+        let (mut output_buf, mut answer_buf) = open()?;
+        let difference = match self.buf_diff(&mut output_buf, &mut answer_buf)? {
+            Some(difference) => difference,
+            None => {
+                return Ok(CheckResult {
+                    verdict: Verdict::Accepted,
+                    score: None,
+                    comment: String::new(),
+                    difference: None,
+                });
+            }
+        };
+
+        let verdict = if self.compare_by_token || self.float_tolerance.is_some() {
+            Verdict::WrongAnswer
+        } else {
+            let (mut output_buf, mut answer_buf) = open()?;
+            match self.token_diff(&mut output_buf, &mut answer_buf)? {
+                Some(_) => Verdict::WrongAnswer,
+                None => Verdict::PresentationError,
+            }
+        };
+        Ok(CheckResult {
+            verdict,
+            score: None,
+            comment: difference.to_string(),
+            difference: Some(difference),
+        })
+    }
+
     /// Check if two buffers is equal line by line.
     ///
     /// Handle the blank line at the end of file
     /// and white space at the end of line
     /// by the checker's options.
     ///
-    /// Return `false` if there is no difference between two buffers.
+    /// Return the first difference between two buffers
+    /// or `None` if there is no difference.
     fn buf_diff<T: BufRead, U: BufRead>(
         &self,
         output_buf: &mut T,
         answer_buf: &mut U,
-    ) -> io::Result<bool> {
+    ) -> io::Result<Option<Difference>> {
+        if self.compare_by_token || self.float_tolerance.is_some() {
+            return self.token_diff(output_buf, answer_buf);
+        }
+
+        let mut line = 0;
         loop {
             let get_line = |buf_reader: &mut dyn BufRead| -> io::Result<Option<Vec<u8>>> {
                 let mut buf = Vec::new();
@@ -121,12 +216,12 @@ impl<'a> Checker<'a> {
                     return Ok(None);
                 }
 
-                if buf.ends_with(&[b'\n']) {
+                if buf.ends_with(b"\n") {
                     buf.pop();
                 }
 
                 if self.ignore_white_space_at_line_end {
-                    while buf.ends_with(&[b' ']) {
+                    while buf.ends_with(b" ") {
                         buf.pop();
                     }
                 }
@@ -134,22 +229,23 @@ impl<'a> Checker<'a> {
                 Ok(Some(buf))
             };
 
+            line += 1;
             match (get_line(output_buf)?, get_line(answer_buf)?) {
                 (Some(output), Some(answer)) => {
                     if output != answer {
-                        return Ok(true);
+                        return Ok(Some(Difference::between_lines(line, &output, &answer)));
                     }
                     continue;
                 }
                 (Some(output), None) => {
                     if !output.is_empty() || !self.ignore_empty_line_at_file_end {
-                        return Ok(true);
+                        return Ok(Some(Difference::between_lines(line, &output, b"")));
                     }
                     continue;
                 }
                 (None, Some(answer)) => {
                     if !answer.is_empty() || !self.ignore_empty_line_at_file_end {
-                        return Ok(true);
+                        return Ok(Some(Difference::between_lines(line, b"", &answer)));
                     }
                     continue;
                 }
@@ -159,8 +255,159 @@ impl<'a> Checker<'a> {
             }
         }
 
-        Ok(false)
+        Ok(None)
     }
+
+    /// Check if two buffers is equal token by token.
+    ///
+    /// Tokens are separated by white space and read one by one,
+    /// so the buffers are never loaded into memory completely.
+    ///
+    /// Return the first difference between two buffers
+    /// or `None` if there is no difference.
+    fn token_diff<T: BufRead, U: BufRead>(
+        &self,
+        output_buf: &mut T,
+        answer_buf: &mut U,
+    ) -> io::Result<Option<Difference>> {
+        let mut output_reader = TokenReader::new(output_buf);
+        let mut answer_reader = TokenReader::new(answer_buf);
+        let mut output = Vec::new();
+        let mut answer = Vec::new();
+        loop {
+            let position = output_reader.read_token(&mut output)?;
+            answer_reader.read_token(&mut answer)?;
+            if output.is_empty() && answer.is_empty() {
+                break;
+            }
+            if !self.token_eq(&output, &answer) {
+                return Ok(Some(Difference {
+                    line: position.0,
+                    column: position.1,
+                    expected: excerpt(&answer, 0),
+                    actual: excerpt(&output, 0),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Check if two tokens are equal.
+    ///
+    /// Numeric tokens are compared with the float tolerance of the checker.
+    fn token_eq(&self, output: &[u8], answer: &[u8]) -> bool {
+        if output == answer {
+            return true;
+        }
+        if let (Some((absolute, relative)), Some(output), Some(answer)) = (
+            self.float_tolerance,
+            parse_number(output),
+            parse_number(answer),
+        ) {
+            let error = (output - answer).abs();
+            return error <= absolute || error <= relative * answer.abs();
+        }
+        false
+    }
+}
+
+/// Open output file and answer file with buffers.
+fn open_files(
+    output_file: &path::Path,
+    answer_file: &path::Path,
+) -> io::Result<(io::BufReader<fs::File>, io::BufReader<fs::File>)> {
+    let output_buf = {
+        let file = fs::File::open(output_file)?;
+        io::BufReader::new(file)
+    };
+
+    let answer_buf = {
+        let file = fs::File::open(answer_file)?;
+        io::BufReader::new(file)
+    };
+
+    Ok((output_buf, answer_buf))
+}
+
+/// A reader which reads white space separated tokens from a buffer
+/// and keeps track of the position.
+struct TokenReader<'b, T: BufRead> {
+    buf: &'b mut T,
+    line: usize,
+    column: usize,
+}
+
+impl<'b, T: BufRead> TokenReader<'b, T> {
+    fn new(buf: &'b mut T) -> TokenReader<'b, T> {
+        TokenReader {
+            buf,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Read the next token into `token`.
+    ///
+    /// Return the line and column where the token starts,
+    /// `token` is empty if there is no more token.
+    fn read_token(&mut self, token: &mut Vec<u8>) -> io::Result<(usize, usize)> {
+        token.clear();
+        let mut position = (self.line, self.column);
+        loop {
+            let (consumed, done) = {
+                let available = self.buf.fill_buf()?;
+                if available.is_empty() {
+                    break;
+                }
+                let mut consumed = 0;
+                let mut done = false;
+                for &byte in available {
+                    if byte.is_ascii_whitespace() || byte == b'\x0b' {
+                        if !token.is_empty() {
+                            done = true;
+                            break;
+                        }
+                    } else {
+                        if token.is_empty() {
+                            position = (self.line, self.column);
+                        }
+                        token.push(byte);
+                    }
+                    if byte == b'\n' {
+                        self.line += 1;
+                        self.column = 1;
+                    } else {
+                        self.column += 1;
+                    }
+                    consumed += 1;
+                }
+                (consumed, done)
+            };
+            self.buf.consume(consumed);
+            if done {
+                break;
+            }
+        }
+        if token.is_empty() {
+            position = (self.line, self.column);
+        }
+        Ok(position)
+    }
+}
+
+/// Parse a token as a decimal number.
+///
+/// Return `None` if the token is not a number,
+/// names like "inf" or "nan" are not considered as numbers.
+fn parse_number(token: &[u8]) -> Option<f64> {
+    if !token.iter().any(u8::is_ascii_digit)
+        || !token
+            .iter()
+            .all(|byte| byte.is_ascii_digit() || b"+-.eE".contains(byte))
+    {
+        return None;
+    }
+    std::str::from_utf8(token).ok()?.parse().ok()
 }
 
 /// Default checker compare two files/strings/bytes line by line and
@@ -170,19 +417,144 @@ impl<'a> Default for Checker<'a> {
         Checker {
             ignore_white_space_at_line_end: true,
             ignore_empty_line_at_file_end: true,
+            compare_by_token: false,
+            float_tolerance: None,
             extern_program: None,
         }
     }
 }
 
+/// Verdict given by a checker.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    Accepted,
+    WrongAnswer,
+    PresentationError,
+    /// The output is partially correct and the score is provided.
+    PartiallyCorrect,
+    /// The checker itself failed, e.g. the answer is wrong.
+    Fail,
+}
+
+/// Result of checking an output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckResult {
+    pub verdict: Verdict,
+    /// Score fraction between `0.0` and `1.0` for partially correct output.
+    pub score: Option<f64>,
+    /// Message from the checker.
+    pub comment: String,
+    /// The first difference found by the builtin comparer.
+    pub difference: Option<Difference>,
+}
+
+/// Location and excerpts of the first difference between output and answer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+    /// Line number in the output, starting from 1.
+    pub line: usize,
+    /// Column number in the output, starting from 1.
+    pub column: usize,
+    /// Excerpt of the answer around the difference.
+    pub expected: String,
+    /// Excerpt of the output around the difference.
+    pub actual: String,
+}
+
+impl Difference {
+    /// Locate the first difference between two different lines.
+    fn between_lines(line: usize, output: &[u8], answer: &[u8]) -> Difference {
+        let index = output
+            .iter()
+            .zip(answer)
+            .position(|(output, answer)| output != answer)
+            .unwrap_or_else(|| output.len().min(answer.len()));
+        Difference {
+            line,
+            column: index + 1,
+            expected: excerpt(answer, index),
+            actual: excerpt(output, index),
+        }
+    }
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Line {}, column {}: expected {:?}, found {:?}",
+            self.line, self.column, self.expected, self.actual
+        )
+    }
+}
+
+/// Maximum number of bytes before and after the difference in an excerpt.
+const EXCERPT_RADIUS: usize = 16;
+
+/// Get the excerpt of `content` around `index`.
+fn excerpt(content: &[u8], index: usize) -> String {
+    let start = index.saturating_sub(EXCERPT_RADIUS).min(content.len());
+    let end = (index + EXCERPT_RADIUS).min(content.len());
+    String::from_utf8_lossy(&content[start..end]).into_owned()
+}
+
+impl CheckResult {
+    /// Parse the result of a testlib checker by its exit status and stderr.
+    ///
+    /// Exit code 0 is accepted, 1 is wrong answer, 2, 4 and 8 are presentation
+    /// error and 7 is partially correct whose score is the number following
+    /// "points" in the message. A score outside `[0, 1]` is a failure
+    /// of the checker. Any other exit status is a failure.
+    pub fn from_testlib(exit_status: process::ExitStatus, stderr: &[u8]) -> CheckResult {
+        let comment = String::from_utf8_lossy(stderr).trim().to_owned();
+        let (verdict, score) = match exit_status.code() {
+            Some(0) => (Verdict::Accepted, None),
+            Some(1) => (Verdict::WrongAnswer, None),
+            Some(2) | Some(4) | Some(8) => (Verdict::PresentationError, None),
+            Some(7) => {
+                let mut words = comment.split_whitespace();
+                let score = match words.next() {
+                    Some("points") => words.next(),
+                    word => word,
+                }
+                .and_then(|score| score.parse::<f64>().ok())
+                .filter(|score| (0.0..=1.0).contains(score));
+                match score {
+                    Some(score) => (Verdict::PartiallyCorrect, Some(score)),
+                    None => (Verdict::Fail, None),
+                }
+            }
+            _ => (Verdict::Fail, None),
+        };
+        CheckResult {
+            verdict,
+            score,
+            comment,
+            difference: None,
+        }
+    }
+
+    /// Return `true` if the output is accepted.
+    pub fn is_accepted(&self) -> bool {
+        self.verdict == Verdict::Accepted
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::process::ExitStatusExt;
+
     use tempfile;
 
     fn diff(output: &[u8], answer: &[u8]) -> bool {
-        !Checker::default().compare_bytes(&output, &answer).unwrap()
+        !Checker::default().compare_bytes(output, answer).unwrap()
+    }
+
+    fn check_result(code: i32, stderr: &str) -> CheckResult {
+        CheckResult::from_testlib(process::ExitStatus::from_raw(code << 8), stderr.as_bytes())
     }
 
     #[test]
@@ -222,6 +594,143 @@ mod tests {
         assert!(!diff(b"hello world\n", b"hello world\n \n"));
     }
 
+    /// A reader generates `count` tokens separated by `separator`.
+    struct TokenStream {
+        count: usize,
+        separator: &'static [u8],
+        pending: Vec<u8>,
+    }
+
+    impl io::Read for TokenStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.pending.is_empty() && self.count > 0 {
+                self.count -= 1;
+                self.pending
+                    .extend_from_slice(self.count.to_string().as_bytes());
+                self.pending.extend_from_slice(self.separator);
+            }
+            let len = buf.len().min(self.pending.len());
+            buf[..len].copy_from_slice(&self.pending[..len]);
+            self.pending.drain(..len);
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_compare_by_token() -> io::Result<()> {
+        let checker = Checker::new().compare_by_token(true);
+        assert!(checker.compare_str("1 2\n3", "1\t2 3\r\n")?);
+        assert!(checker.compare_str("  hello\n\n world  ", "hello world")?);
+        assert!(checker.compare_str("", "\n\n")?);
+        assert!(!checker.compare_str("hello world", "helloworld")?);
+        assert!(!checker.compare_str("1 2 3", "1 2")?);
+        assert!(!checker.compare_str("1.0", "1")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_by_token_stream() -> io::Result<()> {
+        let checker = Checker::new().compare_by_token(true);
+        let stream = |count, separator| {
+            io::BufReader::new(TokenStream {
+                count,
+                separator,
+                pending: Vec::new(),
+            })
+        };
+        assert!(checker
+            .buf_diff(
+                &mut stream(1_000_000, b" "),
+                &mut stream(1_000_000, b"\r\n")
+            )?
+            .is_none());
+        assert!(checker
+            .buf_diff(&mut stream(1_000_000, b" "), &mut stream(999_999, b"\t"))?
+            .is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_float_tolerance() -> io::Result<()> {
+        let checker = Checker::new().float_tolerance(1e-6, 1e-6);
+        assert!(checker.compare_str("3.1415926", "3.14159265358979")?);
+        assert!(checker.compare_str("1000000.5", "1000000.0")?);
+        assert!(checker.compare_str("1e-7 answer", "0 answer")?);
+        assert!(checker.compare_bytes(b"1.0 2.0\n3.0\n", b"1 2 3")?);
+        assert!(!checker.compare_str("3.14", "3.14159265358979")?);
+        assert!(!checker.compare_str("1.0 Answer", "1.0 answer")?);
+        assert!(!checker.compare_str("1.0 2.0", "1.0")?);
+        assert!(!checker.compare_str("nan", "0")?);
+        assert!(!checker.compare_str("inf", "1e400")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_presentation_error() -> io::Result<()> {
+        let checker = Checker::default();
+        assert!(checker.check_str("1 2\n3\n", "1 2\n3")?.is_accepted());
+
+        let result = checker.check_str("1  2\n3\n", "1 2\n3")?;
+        assert_eq!(result.verdict, Verdict::PresentationError);
+        assert_eq!(
+            result.difference,
+            Some(Difference {
+                line: 1,
+                column: 3,
+                expected: String::from("1 2"),
+                actual: String::from("1  2"),
+            })
+        );
+
+        let result = checker.check_str("1 2\n4\n", "1 2\n3")?;
+        assert_eq!(result.verdict, Verdict::WrongAnswer);
+        assert_eq!(
+            result.difference.as_ref().map(|d| (d.line, d.column)),
+            Some((2, 1))
+        );
+        assert_eq!(
+            result.comment,
+            r#"Line 2, column 1: expected "3", found "4""#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_by_token() -> io::Result<()> {
+        let checker = Checker::new().compare_by_token(true);
+        let result = checker.check_str("1 2\n  3 5", "1 2 3 4")?;
+        assert_eq!(result.verdict, Verdict::WrongAnswer);
+        assert_eq!(
+            result.difference,
+            Some(Difference {
+                line: 2,
+                column: 5,
+                expected: String::from("4"),
+                actual: String::from("5"),
+            })
+        );
+
+        let result = checker.check_str("1 2", "1 2 3")?;
+        assert_eq!(
+            result.difference.map(|d| (d.line, d.column, d.actual)),
+            Some((1, 4, String::new()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_files() -> io::Result<()> {
+        let work_dir = tempfile::tempdir()?;
+        let output_file = work_dir.path().join("output");
+        let answer_file = work_dir.path().join("answer");
+        fs::write(&output_file, "hello\tworld")?;
+        fs::write(&answer_file, "hello world")?;
+
+        let result = Checker::default().check_files(&output_file, &answer_file)?;
+        assert_eq!(result.verdict, Verdict::PresentationError);
+        Ok(())
+    }
+
     #[test]
     fn test_check() -> io::Result<()> {
         let work_dir = tempfile::tempdir()?;
@@ -237,4 +746,71 @@ mod tests {
         assert!(!checker.compare_files(&file0, &file2)?);
         Ok(())
     }
+
+    #[test]
+    fn test_testlib_exit_code() {
+        assert_eq!(check_result(0, "ok 1 number").verdict, Verdict::Accepted);
+        assert_eq!(
+            check_result(1, "wrong answer").verdict,
+            Verdict::WrongAnswer
+        );
+        assert_eq!(
+            check_result(2, "wrong output format").verdict,
+            Verdict::PresentationError
+        );
+        assert_eq!(check_result(3, "FAIL").verdict, Verdict::Fail);
+        assert_eq!(
+            CheckResult::from_testlib(process::ExitStatus::from_raw(9), b"").verdict,
+            Verdict::Fail
+        );
+    }
+
+    #[test]
+    fn test_testlib_points() {
+        let result = check_result(7, "points 0.5 half of the answers are correct\n");
+        assert_eq!(result.verdict, Verdict::PartiallyCorrect);
+        assert_eq!(result.score, Some(0.5));
+        assert_eq!(result.comment, "points 0.5 half of the answers are correct");
+
+        assert_eq!(check_result(7, "points").verdict, Verdict::Fail);
+        assert_eq!(check_result(7, "points 50").verdict, Verdict::Fail);
+        assert_eq!(check_result(7, "points -0.5").verdict, Verdict::Fail);
+        assert_eq!(check_result(7, "points NaN").verdict, Verdict::Fail);
+        assert_eq!(check_result(7, "points 1").score, Some(1.0));
+    }
+
+    #[test]
+    fn test_check_without_extern_program() {
+        let path = path::Path::new("/dev/null");
+        match Checker::new().check_use_extern_program(path, path, path) {
+            Err(Error::Checker(_)) => {}
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_check_use_extern_program() -> io::Result<()> {
+        let work_dir = tempfile::tempdir()?;
+        let spj = work_dir.path().join("spj");
+        fs::write(
+            &spj,
+            "#!/bin/sh\ncmp -s \"$2\" \"$3\" && exit 0\necho \"wrong answer $(cat $2)\" >&2\nexit 1\n",
+        )?;
+        fs::set_permissions(&spj, fs::Permissions::from_mode(0o755))?;
+        let input_file = work_dir.path().join("input");
+        let output_file = work_dir.path().join("output");
+        let answer_file = work_dir.path().join("answer");
+        fs::write(&input_file, "")?;
+        fs::write(&output_file, "hello_world")?;
+        fs::write(&answer_file, "hello world")?;
+
+        let checker = Checker::new().extern_program(&spj);
+        let result = checker.check_use_extern_program(&input_file, &output_file, &answer_file)?;
+        assert_eq!(result.verdict, Verdict::WrongAnswer);
+        assert_eq!(result.comment, "wrong answer hello_world");
+
+        let result = checker.check_use_extern_program(&input_file, &answer_file, &answer_file)?;
+        assert!(result.is_accepted());
+        Ok(())
+    }
 }
@@ -1,11 +1,12 @@
 mod backends;
 
 use std::ffi::OsString;
-use std::io::{self, prelude::*};
+use std::io::prelude::*;
 use std::path::Path;
 use std::process::{Command, Output, Stdio};
 use std::time::Duration;
 
+use crate::error::{Error, Result};
 use crate::executor::ChildExt as _;
 use crate::structures::Source;
 
@@ -41,7 +42,7 @@ impl<'a> Compiler<'a> {
     ///
     /// Return an `Err` if there is a configuration for the `language`
     /// but the configuration is unavailable or there is an io error.
-    pub fn new(language: &str) -> Option<Compiler> {
+    pub fn new(language: &str) -> Option<Compiler<'_>> {
         Some(Compiler::from(backends::get_config(language)?))
     }
 
@@ -53,7 +54,7 @@ impl<'a> Compiler<'a> {
     ///
     /// Return the result of the compiler process,
     /// or return `Err` if the command run incorrectly.
-    pub fn compile(&self, source: &Source, executable_file: &Path) -> io::Result<Output> {
+    pub fn compile(&self, source: &Source, executable_file: &Path) -> Result<Output> {
         let source_file = {
             let mut res = tempfile::Builder::new()
                 .prefix("source_")
@@ -63,7 +64,7 @@ impl<'a> Compiler<'a> {
             res.into_temp_path()
         };
 
-        let output = Command::new(&self.command)
+        let output = Command::new(self.command)
             .args(self.args.iter().map(|arg| match arg.as_str() {
                 "{source_file}" => source_file.as_os_str().to_owned(),
                 "{executable_file}" => executable_file.as_os_str().to_owned(),
@@ -72,7 +73,8 @@ impl<'a> Compiler<'a> {
             .stdin(Stdio::null())
             .stdout(Stdio::null())
             .stderr(Stdio::piped())
-            .spawn()?
+            .spawn()
+            .map_err(|e| Error::Compile(format!("Failed to run {}: {}", self.command, e)))?
             .timeout_with_output(self.timeout)?;
 
         Ok(output)
@@ -0,0 +1,63 @@
+//! Error type for all fallible operations of the crate.
+use std::error;
+use std::fmt::{self, Display};
+use std::io;
+
+/// All kinds of error which may occur while judging.
+#[derive(Debug)]
+pub enum Error {
+    /// An io error from the operating system.
+    Io(io::Error),
+    /// A cgroup attribute file is unavailable or malformed.
+    Cgroup(String),
+    /// A seccomp rule is invalid or libseccomp failed.
+    Seccomp(String),
+    /// The compiler can not be run.
+    Compile(String),
+    /// The checker can not check the output.
+    Checker(String),
+    /// A configuration is invalid.
+    Config(String),
+}
+
+/// Result type whose error is `liboj::Error`.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::Cgroup(message) => write!(f, "Cgroup error: {}", message),
+            Error::Seccomp(message) => write!(f, "Seccomp error: {}", message),
+            Error::Compile(message) => write!(f, "Compile error: {}", message),
+            Error::Checker(message) => write!(f, "Checker error: {}", message),
+            Error::Config(message) => write!(f, "Config error: {}", message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+/// Convert the error into an io error,
+/// so it can be returned by closures such as `pre_exec`.
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        match e {
+            Error::Io(e) => e,
+            e => io::Error::other(e),
+        }
+    }
+}
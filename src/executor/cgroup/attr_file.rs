@@ -5,17 +5,18 @@
 //! This is widely used in cgroup filesystem.
 use std::fmt::Debug;
 use std::fs::{read_to_string, write};
-use std::io;
 use std::path::Path;
 use std::str::FromStr;
 
+use crate::error::{Error, Result};
+
 /// A file which can be written and read.
 pub trait AttrFile<'a, T, U> {
     /// Write a attribute to the file.
-    fn write(&mut self, attr: &T) -> io::Result<()>;
+    fn write(&mut self, attr: &T) -> Result<()>;
 
     /// Write a attribute to the file.
-    fn read(&self) -> io::Result<U>;
+    fn read(&self) -> Result<U>;
 }
 
 /// There is a default implementation of `AttrFile`
@@ -27,16 +28,129 @@ where
     U::Err: Debug,
     P: AsRef<Path>,
 {
-    fn write(&mut self, attr: &T) -> io::Result<()> {
-        write(&self, &attr.to_string())?;
+    fn write(&mut self, attr: &T) -> Result<()> {
+        write(self, attr.to_string())?;
+        Ok(())
+    }
+
+    fn read(&self) -> Result<U> {
+        read_attr(self.as_ref())
+    }
+}
+
+/// Read the attribute from the file.
+pub(crate) fn read_attr<U>(file: &Path) -> Result<U>
+where
+    U: FromStr,
+    U::Err: Debug,
+{
+    parse_attr(file, read_to_string(file)?.trim())
+}
+
+/// Parse an attribute read from the file.
+///
+/// Return an `Err` with the file path if the attribute is malformed.
+pub(crate) fn parse_attr<U>(file: &Path, attr: &str) -> Result<U>
+where
+    U: FromStr,
+    U::Err: Debug,
+{
+    attr.parse().map_err(|e| {
+        Error::Cgroup(format!(
+            "Failed to parse {:?} in {}: {:?}",
+            attr,
+            file.display(),
+            e
+        ))
+    })
+}
+
+/// Read the value of `key` from a flat keyed file,
+/// such as `cpu.stat` and `memory.events` whose lines are "key value".
+pub(crate) fn read_keyed_attr<U>(file: &Path, key: &str) -> Result<U>
+where
+    U: FromStr,
+    U::Err: Debug,
+{
+    let content = read_to_string(file)?;
+    for line in content.lines() {
+        let mut words = line.split_whitespace();
+        if words.next() == Some(key) {
+            return parse_attr(file, words.next().unwrap_or_default());
+        }
+    }
+    Err(Error::Cgroup(format!(
+        "No such key {:?} in {}",
+        key,
+        file.display()
+    )))
+}
+
+/// A file containing a limit or "max" if there is no limit,
+/// such as `memory.max` and `pids.max` in cgroup v2.
+///
+/// No limit is represented by `usize::MAX`.
+pub(crate) struct MaxFile<P: AsRef<Path>> {
+    inner: P,
+}
+
+impl<P: AsRef<Path>> From<P> for MaxFile<P> {
+    fn from(inner: P) -> MaxFile<P> {
+        MaxFile { inner }
+    }
+}
+
+impl<'a, P: AsRef<Path>> AttrFile<'a, usize, usize> for MaxFile<P> {
+    fn write(&mut self, attr: &usize) -> Result<()> {
+        if *attr == usize::MAX {
+            write(&self.inner, "max")?;
+        } else {
+            write(&self.inner, attr.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn read(&self) -> Result<usize> {
+        let file = self.inner.as_ref();
+        match read_to_string(file)?.trim() {
+            "max" => Ok(usize::MAX),
+            attr => parse_attr(file, attr),
+        }
+    }
+}
+
+/// A file containing a list of indices such as "0-3,6".
+pub(crate) struct ListFile<T: AsRef<Path>> {
+    inner: T,
+}
+
+impl<T: AsRef<Path>> From<T> for ListFile<T> {
+    fn from(inner: T) -> ListFile<T> {
+        ListFile { inner }
+    }
+}
+
+impl<'a, T: AsRef<Path>> AttrFile<'a, Vec<usize>, Vec<usize>> for ListFile<T> {
+    fn write(&mut self, list: &Vec<usize>) -> Result<()> {
+        let list: Vec<String> = list.iter().map(usize::to_string).collect();
+        write(&self.inner, list.join(","))?;
         Ok(())
     }
 
-    fn read(&self) -> io::Result<U> {
-        let attr = read_to_string(&self)?
-            .trim()
-            .parse()
-            .expect("Failed to read the value from the given file");
-        Ok(attr)
+    fn read(&self) -> Result<Vec<usize>> {
+        let file = self.inner.as_ref();
+        let mut list = Vec::new();
+        for range in read_to_string(file)?.trim().split(',') {
+            match range.split_once('-') {
+                Some((first, last)) => {
+                    let first: usize = parse_attr(file, first)?;
+                    let last: usize = parse_attr(file, last)?;
+                    list.extend(first..=last);
+                }
+                None if range.is_empty() => {}
+                None => list.push(parse_attr(file, range)?),
+            }
+        }
+        Ok(list)
     }
 }
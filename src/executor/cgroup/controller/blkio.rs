@@ -0,0 +1,281 @@
+use std::fmt::{self, Display};
+use std::fs::{metadata, read_to_string, write};
+use std::marker::PhantomData;
+use std::os::unix::fs::MetadataExt as _;
+use std::path::Path;
+use std::str::FromStr;
+
+use nix::sys::stat::{major, minor};
+
+use super::*;
+
+/// Block IO controller.
+///
+/// It is the `blkio` controller in v1 and the `io` controller in v2.
+/// Limits are only applied to whole disks instead of partitions.
+pub struct BlkioController<'a, T: 'a + AsRef<Path>> {
+    inner: T,
+    version: Version,
+    baseline: &'a Baseline,
+    _mark: PhantomData<&'a ()>,
+}
+
+impl<'a, T: 'a + AsRef<Path>> BlkioController<'a, T> {
+    /// `blkio.throttle.read_bps_device` in v1 or `rbps` in `io.max` in v2.
+    ///
+    /// Write a limit of zero to remove the limit of the device.
+    pub fn read_bps_device(&'a self) -> Box<dyn AttrFile<'a, DeviceLimit, Vec<DeviceLimit>> + 'a> {
+        Box::new(self.limit_file("read_bps_device", "rbps"))
+    }
+
+    /// `blkio.throttle.write_bps_device` in v1 or `wbps` in `io.max` in v2.
+    ///
+    /// Write a limit of zero to remove the limit of the device.
+    pub fn write_bps_device(&'a self) -> Box<dyn AttrFile<'a, DeviceLimit, Vec<DeviceLimit>> + 'a> {
+        Box::new(self.limit_file("write_bps_device", "wbps"))
+    }
+
+    /// `blkio.throttle.read_iops_device` in v1 or `riops` in `io.max` in v2.
+    ///
+    /// Write a limit of zero to remove the limit of the device.
+    pub fn read_iops_device(&'a self) -> Box<dyn AttrFile<'a, DeviceLimit, Vec<DeviceLimit>> + 'a> {
+        Box::new(self.limit_file("read_iops_device", "riops"))
+    }
+
+    /// `blkio.throttle.write_iops_device` in v1 or `wiops` in `io.max` in v2.
+    ///
+    /// Write a limit of zero to remove the limit of the device.
+    pub fn write_iops_device(
+        &'a self,
+    ) -> Box<dyn AttrFile<'a, DeviceLimit, Vec<DeviceLimit>> + 'a> {
+        Box::new(self.limit_file("write_iops_device", "wiops"))
+    }
+
+    /// IO usage of each device read from `blkio.throttle.io_service_bytes`
+    /// and `blkio.throttle.io_serviced` in v1 or `io.stat` in v2.
+    ///
+    /// The usage in v2 is relative to the usage recorded by `Context::reset`.
+    pub fn stat(&self) -> Result<Vec<(Device, IoStat)>> {
+        let mut stats: Vec<(Device, IoStat)> = Vec::new();
+        match self.version {
+            Version::V1 => {
+                for (attr, is_bytes) in &[("io_service_bytes", true), ("io_serviced", false)] {
+                    let file = self.inner.as_ref().join(format!("blkio.throttle.{}", attr));
+                    for line in read_to_string(&file)?.lines() {
+                        // Lines are "major:minor operation value" except the last "Total value"
+                        let words: Vec<&str> = line.split_whitespace().collect();
+                        let (device, operation, value) = match words.as_slice() {
+                            [device, operation, value] => (device, operation, value),
+                            _ => continue,
+                        };
+                        let device: Device = parse_attr(&file, device)?;
+                        let value: u64 = parse_attr(&file, value)?;
+                        let stat = match stats.iter().position(|(d, _)| *d == device) {
+                            Some(i) => &mut stats[i].1,
+                            None => {
+                                stats.push((device, IoStat::default()));
+                                &mut stats.last_mut().unwrap().1
+                            }
+                        };
+                        match (*operation, is_bytes) {
+                            ("Read", true) => stat.read_bytes = value,
+                            ("Write", true) => stat.write_bytes = value,
+                            ("Read", false) => stat.read_ios = value,
+                            ("Write", false) => stat.write_ios = value,
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            Version::V2 => {
+                let file = self.inner.as_ref().join("io.stat");
+                for line in read_to_string(&file)?.lines() {
+                    // Lines are "major:minor rbytes=1 wbytes=2 rios=3 wios=4 ..."
+                    let mut words = line.split_whitespace();
+                    let device = match words.next() {
+                        Some(device) => parse_attr(&file, device)?,
+                        None => continue,
+                    };
+                    let mut stat = IoStat::default();
+                    for (key, value) in words.filter_map(|word| word.split_once('=')) {
+                        match key {
+                            "rbytes" => stat.read_bytes = parse_attr(&file, value)?,
+                            "wbytes" => stat.write_bytes = parse_attr(&file, value)?,
+                            "rios" => stat.read_ios = parse_attr(&file, value)?,
+                            "wios" => stat.write_ios = parse_attr(&file, value)?,
+                            _ => {}
+                        }
+                    }
+                    let baseline = self
+                        .baseline
+                        .io_stat
+                        .iter()
+                        .find(|(d, _)| *d == device)
+                        .map(|(_, stat)| *stat)
+                        .unwrap_or_default();
+                    stats.push((device, stat.since(baseline)));
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Reset the IO usage returned by `stat`.
+    ///
+    /// Only supported by v1, see `Context::reset` for v2.
+    pub fn reset(&self) -> Result<()> {
+        if self.version == Version::V2 {
+            return Err(unsupported_in_v2("Resetting IO usage"));
+        }
+        write(self.inner.as_ref().join("blkio.reset_stats"), "1")?;
+        Ok(())
+    }
+
+    fn limit_file(&self, v1_attr: &str, v2_key: &'static str) -> DeviceLimitFile {
+        match self.version {
+            Version::V1 => DeviceLimitFile {
+                inner: self
+                    .inner
+                    .as_ref()
+                    .join(format!("blkio.throttle.{}", v1_attr)),
+                key: None,
+            },
+            Version::V2 => DeviceLimitFile {
+                inner: self.inner.as_ref().join("io.max"),
+                key: Some(v2_key),
+            },
+        }
+    }
+}
+
+impl<'a> Controller<'a> for BlkioController<'a, PathBuf> {
+    const NAME: &'static str = "blkio";
+
+    fn from_ctx(context: &Context) -> BlkioController<'_, PathBuf> {
+        BlkioController {
+            inner: context.path(Self::NAME),
+            version: context.version,
+            baseline: &context.baseline,
+            _mark: PhantomData,
+        }
+    }
+
+    fn initialize(&self) -> Result<()> {
+        create_hierarchy(&self.inner, self.version, Some("io"))
+    }
+}
+
+impl<'a, T: 'a + AsRef<Path>> AsRef<Path> for BlkioController<'a, T> {
+    fn as_ref(&self) -> &Path {
+        self.inner.as_ref()
+    }
+}
+
+/// A block device identified by its major and minor numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Device {
+    pub major: u64,
+    pub minor: u64,
+}
+
+impl Device {
+    pub fn new(major: u64, minor: u64) -> Device {
+        Device { major, minor }
+    }
+
+    /// The device of the filesystem containing `path`.
+    ///
+    /// It may be a partition whose limits are not applied.
+    pub fn of<P: AsRef<Path>>(path: P) -> Result<Device> {
+        let dev = metadata(path)?.dev();
+        Ok(Device::new(major(dev), minor(dev)))
+    }
+}
+
+impl Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for Device {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Device, String> {
+        let (major, minor) = s
+            .split_once(':')
+            .ok_or_else(|| String::from("Missing ':' between major and minor"))?;
+        Ok(Device {
+            major: major.parse().map_err(|e| format!("Invalid major: {}", e))?,
+            minor: minor.parse().map_err(|e| format!("Invalid minor: {}", e))?,
+        })
+    }
+}
+
+/// Limit of a device, in bytes or operations per second.
+pub type DeviceLimit = (Device, u64);
+
+/// IO usage of a device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoStat {
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub read_ios: u64,
+    pub write_ios: u64,
+}
+
+impl IoStat {
+    /// Usage since `baseline` was recorded.
+    fn since(self, baseline: IoStat) -> IoStat {
+        IoStat {
+            read_bytes: self.read_bytes.saturating_sub(baseline.read_bytes),
+            write_bytes: self.write_bytes.saturating_sub(baseline.write_bytes),
+            read_ios: self.read_ios.saturating_sub(baseline.read_ios),
+            write_ios: self.write_ios.saturating_sub(baseline.write_ios),
+        }
+    }
+}
+
+/// A file of device limits.
+///
+/// In v1 each line is "major:minor limit",
+/// while in v2 all limits are in `io.max` as "major:minor key=limit ...".
+struct DeviceLimitFile {
+    inner: PathBuf,
+    key: Option<&'static str>,
+}
+
+impl<'a> AttrFile<'a, DeviceLimit, Vec<DeviceLimit>> for DeviceLimitFile {
+    fn write(&mut self, (device, limit): &DeviceLimit) -> Result<()> {
+        let attr = match self.key {
+            None => format!("{} {}", device, limit),
+            Some(key) if *limit == 0 => format!("{} {}=max", device, key),
+            Some(key) => format!("{} {}={}", device, key, limit),
+        };
+        write(&self.inner, attr)?;
+        Ok(())
+    }
+
+    fn read(&self) -> Result<Vec<DeviceLimit>> {
+        let mut limits = Vec::new();
+        for line in read_to_string(&self.inner)?.lines() {
+            let mut words = line.split_whitespace();
+            let device = match words.next() {
+                Some(device) => parse_attr(&self.inner, device)?,
+                None => continue,
+            };
+            let limit = match self.key {
+                None => words.next(),
+                Some(key) => words
+                    .filter_map(|word| word.split_once('='))
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, limit)| limit),
+            };
+            match limit {
+                Some("max") | None => {}
+                Some(limit) => limits.push((device, parse_attr(&self.inner, limit)?)),
+            }
+        }
+        Ok(limits)
+    }
+}
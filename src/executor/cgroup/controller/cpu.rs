@@ -1,5 +1,4 @@
-use std::fs::{create_dir, read_to_string, write};
-use std::io;
+use std::fs::{read_to_string, write};
 use std::marker::PhantomData;
 use std::path::Path;
 use std::time::Duration;
@@ -8,42 +7,55 @@ use super::*;
 
 pub struct CpuController<'a, T: 'a + AsRef<Path>> {
     inner: T,
+    version: Version,
     _mark: PhantomData<&'a ()>,
 }
 
 impl<'a, T: 'a + AsRef<Path>> CpuController<'a, T> {
+    /// `cpu.cfs_period_us` in v1 or the period in `cpu.max` in v2.
     pub fn period(&'a self) -> Box<dyn AttrFile<'a, Duration, Duration> + 'a> {
-        Box::new(CpuTimeFile {
-            inner: self.inner.as_ref().join("cpu.cfs_period_us"),
-            _mark: PhantomData,
-        })
+        match self.version {
+            Version::V1 => Box::new(CpuTimeFile {
+                inner: self.inner.as_ref().join("cpu.cfs_period_us"),
+                _mark: PhantomData,
+            }),
+            Version::V2 => Box::new(CpuMaxFile {
+                inner: self.inner.as_ref().join("cpu.max"),
+                field: CpuMaxField::Period,
+            }),
+        }
     }
 
+    /// `cpu.cfs_quota_us` in v1 or the quota in `cpu.max` in v2.
+    ///
+    /// In v2, no quota is represented by `Duration::MAX`.
     pub fn quota(&'a self) -> Box<dyn AttrFile<'a, Duration, Duration> + 'a> {
-        Box::new(CpuTimeFile {
-            inner: self.inner.as_ref().join("cpu.cfs_quota_us"),
-            _mark: PhantomData,
-        })
+        match self.version {
+            Version::V1 => Box::new(CpuTimeFile {
+                inner: self.inner.as_ref().join("cpu.cfs_quota_us"),
+                _mark: PhantomData,
+            }),
+            Version::V2 => Box::new(CpuMaxFile {
+                inner: self.inner.as_ref().join("cpu.max"),
+                field: CpuMaxField::Quota,
+            }),
+        }
     }
 }
 
 impl<'a> Controller<'a> for CpuController<'a, PathBuf> {
     const NAME: &'static str = "cpu";
 
-    fn from_ctx(context: &Context) -> CpuController<PathBuf> {
+    fn from_ctx(context: &Context) -> CpuController<'_, PathBuf> {
         CpuController {
-            inner: Context::root().join(Self::NAME).join(&context.name),
+            inner: context.path(Self::NAME),
+            version: context.version,
             _mark: PhantomData,
         }
     }
 
-    fn initialize(&self) -> io::Result<()> {
-        match create_dir(&self.inner) {
-            Ok(_) => {}
-            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {}
-            Err(e) => return Err(e),
-        }
-        Ok(())
+    fn initialize(&self) -> Result<()> {
+        create_hierarchy(&self.inner, self.version, Some(Self::NAME))
     }
 }
 
@@ -59,13 +71,63 @@ struct CpuTimeFile<'a, T: 'a + AsRef<Path>> {
 }
 
 impl<'a, T: 'a + AsRef<Path>> AttrFile<'a, Duration, Duration> for CpuTimeFile<'a, T> {
-    fn read(&self) -> io::Result<Duration> {
-        let attr: u64 = read_to_string(&self.inner)?.trim().parse().unwrap();
+    fn read(&self) -> Result<Duration> {
+        let attr = read_attr(self.inner.as_ref())?;
         Ok(Duration::from_micros(attr))
     }
 
-    fn write(&mut self, attr: &Duration) -> io::Result<()> {
+    fn write(&mut self, attr: &Duration) -> Result<()> {
         write(&self.inner, attr.as_micros().to_string())?;
         Ok(())
     }
 }
+
+#[derive(Clone, Copy)]
+enum CpuMaxField {
+    Quota,
+    Period,
+}
+
+/// `cpu.max` in cgroup v2, which is formatted as "$MAX $PERIOD".
+struct CpuMaxFile {
+    inner: PathBuf,
+    field: CpuMaxField,
+}
+
+impl CpuMaxFile {
+    /// Read the quota and the period in microseconds.
+    fn read_max(&self) -> Result<(Option<u128>, u128)> {
+        let content = read_to_string(&self.inner)?;
+        let mut words = content.split_whitespace();
+        let quota = match words.next() {
+            Some("max") => None,
+            quota => Some(parse_attr(&self.inner, quota.unwrap_or_default())?),
+        };
+        let period = parse_attr(&self.inner, words.next().unwrap_or_default())?;
+        Ok((quota, period))
+    }
+}
+
+impl<'a> AttrFile<'a, Duration, Duration> for CpuMaxFile {
+    fn read(&self) -> Result<Duration> {
+        let (quota, period) = self.read_max()?;
+        match self.field {
+            CpuMaxField::Quota => {
+                Ok(quota.map_or(Duration::MAX, |quota| Duration::from_micros(quota as u64)))
+            }
+            CpuMaxField::Period => Ok(Duration::from_micros(period as u64)),
+        }
+    }
+
+    fn write(&mut self, attr: &Duration) -> Result<()> {
+        let (mut quota, mut period) = self.read_max()?;
+        match self.field {
+            CpuMaxField::Quota if *attr == Duration::MAX => quota = None,
+            CpuMaxField::Quota => quota = Some(attr.as_micros()),
+            CpuMaxField::Period => period = attr.as_micros(),
+        }
+        let quota = quota.map_or_else(|| String::from("max"), |quota| quota.to_string());
+        write(&self.inner, format!("{} {}", quota, period))?;
+        Ok(())
+    }
+}
@@ -1,108 +1,146 @@
-use std::fs::{create_dir, read_to_string};
-use std::io;
+use std::fs::{read_to_string, write};
 use std::marker::PhantomData;
 use std::path::Path;
 use std::time::Duration;
 
 use super::*;
 
+/// CPU accounting controller.
+///
+/// In cgroup v2, the usage is read from `cpu.stat` relative to the usage
+/// recorded by `Context::reset`, and the usage of each cpu is not available.
 pub struct CpuAcctController<'a, T: 'a + AsRef<Path>> {
     inner: T,
+    version: Version,
+    baseline: &'a Baseline,
     _mark: PhantomData<&'a ()>,
 }
 
 impl<'a, T: 'a + AsRef<Path>> CpuAcctController<'a, T> {
-    pub fn usage(&self) -> io::Result<Duration> {
+    pub fn usage(&self) -> Result<Duration> {
+        if self.version == Version::V2 {
+            return Ok(self
+                .cpu_stat("usage_usec")?
+                .saturating_sub(self.baseline.cpu_usage));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage");
-        let usage = read_to_string(&file)?.trim().parse().unwrap();
+        let usage = read_attr(&file)?;
         Ok(Duration::from_nanos(usage))
     }
 
-    pub fn usage_all(&self) -> io::Result<Vec<(usize, Duration, Duration)>> {
+    pub fn usage_all(&self) -> Result<Vec<(usize, Duration, Duration)>> {
+        if self.version == Version::V2 {
+            return Err(unsupported_in_v2("cpuacct.usage_all"));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_all");
-        let usages = read_to_string(&file)?
+        read_to_string(&file)?
             .lines()
             .skip(1) // The first line is "cpu user system"
             .map(|line| line.split_whitespace().collect())
-            .map(|line: Vec<&str>| (line[0], line[1], line[2]))
-            .map(|(index, usage_user, usage_sys)| {
-                (
-                    index.parse().unwrap(),
-                    usage_user.parse().unwrap(),
-                    usage_sys.parse().unwrap(),
-                )
+            .map(|line: Vec<&str>| match line.as_slice() {
+                [index, usage_user, usage_sys] => Ok((
+                    parse_attr(&file, index)?,
+                    Duration::from_nanos(parse_attr(&file, usage_user)?),
+                    Duration::from_nanos(parse_attr(&file, usage_sys)?),
+                )),
+                _ => Err(Error::Cgroup(format!(
+                    "Malformed line {:?} in {}",
+                    line.join(" "),
+                    file.display()
+                ))),
             })
-            .map(|(index, usage_user, usage_sys)| {
-                (
-                    index,
-                    Duration::from_nanos(usage_user),
-                    Duration::from_nanos(usage_sys),
-                )
-            })
-            .collect();
-        Ok(usages)
+            .collect()
     }
 
-    pub fn usage_percpu(&self) -> io::Result<Vec<Duration>> {
+    pub fn usage_percpu(&self) -> Result<Vec<Duration>> {
+        if self.version == Version::V2 {
+            return Err(unsupported_in_v2("cpuacct.usage_percpu"));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_percpu");
-        let usages = read_to_string(&file)?
+        read_to_string(&file)?
             .split_whitespace()
-            .map(|usage| usage.parse().unwrap())
-            .map(Duration::from_nanos)
-            .collect();
-        Ok(usages)
+            .map(|usage| Ok(Duration::from_nanos(parse_attr(&file, usage)?)))
+            .collect()
     }
 
-    pub fn usage_percpu_sys(&self) -> io::Result<Vec<Duration>> {
+    pub fn usage_percpu_sys(&self) -> Result<Vec<Duration>> {
+        if self.version == Version::V2 {
+            return Err(unsupported_in_v2("cpuacct.usage_percpu_sys"));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_percpu_sys");
-        let usages = read_to_string(&file)?
+        read_to_string(&file)?
             .split_whitespace()
-            .map(|usage| usage.parse().unwrap())
-            .map(Duration::from_nanos)
-            .collect();
-        Ok(usages)
+            .map(|usage| Ok(Duration::from_nanos(parse_attr(&file, usage)?)))
+            .collect()
     }
 
-    pub fn usage_percpu_user(&self) -> io::Result<Vec<Duration>> {
+    pub fn usage_percpu_user(&self) -> Result<Vec<Duration>> {
+        if self.version == Version::V2 {
+            return Err(unsupported_in_v2("cpuacct.usage_percpu_user"));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_percpu_user");
-        let usages = read_to_string(&file)?
+        read_to_string(&file)?
             .split_whitespace()
-            .map(|usage| usage.parse().unwrap())
-            .map(Duration::from_nanos)
-            .collect();
-        Ok(usages)
+            .map(|usage| Ok(Duration::from_nanos(parse_attr(&file, usage)?)))
+            .collect()
     }
 
-    pub fn usage_sys(&self) -> io::Result<Duration> {
+    pub fn usage_sys(&self) -> Result<Duration> {
+        if self.version == Version::V2 {
+            return Ok(self
+                .cpu_stat("system_usec")?
+                .saturating_sub(self.baseline.cpu_system));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_sys");
-        let usage = read_to_string(&file)?.trim().parse().unwrap();
+        let usage = read_attr(&file)?;
         Ok(Duration::from_nanos(usage))
     }
 
-    pub fn usage_user(&self) -> io::Result<Duration> {
+    pub fn usage_user(&self) -> Result<Duration> {
+        if self.version == Version::V2 {
+            return Ok(self
+                .cpu_stat("user_usec")?
+                .saturating_sub(self.baseline.cpu_user));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_user");
-        let usage = read_to_string(&file)?.trim().parse().unwrap();
+        let usage = read_attr(&file)?;
         Ok(Duration::from_nanos(usage))
     }
+
+    /// Reset all usage counters to zero.
+    ///
+    /// Only supported by v1, see `Context::reset` for v2.
+    pub fn reset(&self) -> Result<()> {
+        if self.version == Version::V2 {
+            return Err(unsupported_in_v2("Resetting cpu usage"));
+        }
+        write(self.inner.as_ref().join("cpuacct.usage"), "0")?;
+        Ok(())
+    }
+
+    /// Read a field in microseconds from `cpu.stat` in cgroup v2.
+    fn cpu_stat(&self, key: &str) -> Result<Duration> {
+        let file = self.inner.as_ref().join("cpu.stat");
+        let usage = read_keyed_attr(&file, key)?;
+        Ok(Duration::from_micros(usage))
+    }
 }
 
 impl<'a> Controller<'a> for CpuAcctController<'a, PathBuf> {
     const NAME: &'static str = "cpuacct";
 
-    fn from_ctx(context: &Context) -> CpuAcctController<PathBuf> {
+    fn from_ctx(context: &Context) -> CpuAcctController<'_, PathBuf> {
         CpuAcctController {
-            inner: Context::root().join(Self::NAME).join(&context.name),
+            inner: context.path(Self::NAME),
+            version: context.version,
+            baseline: &context.baseline,
             _mark: PhantomData,
         }
     }
 
-    fn initialize(&self) -> io::Result<()> {
-        match create_dir(&self.inner) {
-            Ok(_) => {}
-            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {}
-            Err(e) => return Err(e),
-        }
-        Ok(())
+    fn initialize(&self) -> Result<()> {
+        // `cpu.stat` is always available in cgroup v2
+        create_hierarchy(&self.inner, self.version, None)
     }
 }
 
@@ -0,0 +1,68 @@
+use std::fs::{read_to_string, write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use super::*;
+
+/// Cpu and memory node controller.
+pub struct CpusetController<'a, T: 'a + AsRef<Path>> {
+    inner: T,
+    version: Version,
+    _mark: PhantomData<&'a ()>,
+}
+
+impl<'a, T: 'a + AsRef<Path>> CpusetController<'a, T> {
+    /// `cpuset.cpus`, the cpus that processes can run on.
+    pub fn cpus(&'a self) -> Box<dyn AttrFile<'a, Vec<usize>, Vec<usize>> + 'a> {
+        Box::new(ListFile::from(self.inner.as_ref().join("cpuset.cpus")))
+    }
+
+    /// `cpuset.mems`, the memory nodes that processes can use.
+    pub fn mems(&'a self) -> Box<dyn AttrFile<'a, Vec<usize>, Vec<usize>> + 'a> {
+        Box::new(ListFile::from(self.inner.as_ref().join("cpuset.mems")))
+    }
+
+    /// `cpuset.effective_cpus`, the cpus actually granted by the parent.
+    pub fn effective_cpus(&self) -> Result<Vec<usize>> {
+        ListFile::from(self.inner.as_ref().join("cpuset.effective_cpus")).read()
+    }
+
+    /// `cpuset.effective_mems`, the memory nodes actually granted by the parent.
+    pub fn effective_mems(&self) -> Result<Vec<usize>> {
+        ListFile::from(self.inner.as_ref().join("cpuset.effective_mems")).read()
+    }
+}
+
+impl<'a> Controller<'a> for CpusetController<'a, PathBuf> {
+    const NAME: &'static str = "cpuset";
+
+    fn from_ctx(context: &Context) -> CpusetController<'_, PathBuf> {
+        CpusetController {
+            inner: context.path(Self::NAME),
+            version: context.version,
+            _mark: PhantomData,
+        }
+    }
+
+    fn initialize(&self) -> Result<()> {
+        create_hierarchy(&self.inner, self.version, Some(Self::NAME))?;
+
+        // A new cpuset in v1 has no cpu and memory node,
+        // so no process can be attached to it until they are set.
+        if let (Version::V1, Some(parent)) = (self.version, self.inner.parent()) {
+            for attr in &["cpuset.cpus", "cpuset.mems"] {
+                let file = self.inner.join(attr);
+                if read_to_string(&file)?.trim().is_empty() {
+                    write(&file, read_to_string(parent.join(attr))?.trim())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, T: 'a + AsRef<Path>> AsRef<Path> for CpusetController<'a, T> {
+    fn as_ref(&self) -> &Path {
+        self.inner.as_ref()
+    }
+}
@@ -1,60 +1,199 @@
-use std::fs::{create_dir, read_to_string};
-use std::io;
+use std::fs::write;
 use std::marker::PhantomData;
+use std::os::unix::fs::FileExt as _;
 use std::path::Path;
 
 use super::*;
 
+/// Memory controller.
+///
+/// Attributes are named after cgroup v1,
+/// and are mapped to the corresponding files in cgroup v2.
 pub struct MemoryController<'a, T: 'a + AsRef<Path>> {
     inner: T,
+    version: Version,
+    baseline: &'a Baseline,
     _mark: PhantomData<&'a ()>,
 }
 
 impl<'a, T: AsRef<Path>> MemoryController<'a, T> {
-    pub fn usage_in_bytes(&self) -> io::Result<usize> {
-        let file = self.inner.as_ref().join("memory.usage_in_bytes");
-        let usage = read_to_string(&file)?.trim().parse().unwrap();
-        Ok(usage)
+    /// `memory.usage_in_bytes` in v1 or `memory.current` in v2.
+    pub fn usage_in_bytes(&self) -> Result<usize> {
+        let file = match self.version {
+            Version::V1 => self.inner.as_ref().join("memory.usage_in_bytes"),
+            Version::V2 => self.inner.as_ref().join("memory.current"),
+        };
+        read_attr(&file)
     }
 
-    pub fn max_usage_in_bytes(&self) -> io::Result<usize> {
-        let file = self.inner.as_ref().join("memory.max_usage_in_bytes");
-        let usage = read_to_string(&file)?.trim().parse().unwrap();
-        Ok(usage)
+    /// `memory.max_usage_in_bytes` in v1 or `memory.peak` in v2.
+    ///
+    /// The peak in v2 is read through the file reset by `Context::reset` if any.
+    pub fn max_usage_in_bytes(&self) -> Result<usize> {
+        let file = match self.version {
+            Version::V1 => self.inner.as_ref().join("memory.max_usage_in_bytes"),
+            Version::V2 => self.inner.as_ref().join("memory.peak"),
+        };
+        match &self.baseline.memory_peak {
+            Some(peak) => {
+                let mut buf = [0; 32];
+                let len = peak.read_at(&mut buf, 0)?;
+                parse_attr(&file, String::from_utf8_lossy(&buf[..len]).trim())
+            }
+            None => read_attr(&file),
+        }
     }
 
+    /// `memory.limit_in_bytes` in v1 or `memory.max` in v2.
     pub fn limit_in_bytes(&'a self) -> Box<dyn AttrFile<'a, usize, usize> + 'a> {
-        Box::new(self.inner.as_ref().join("memory.limit_in_bytes"))
+        match self.version {
+            Version::V1 => Box::new(self.inner.as_ref().join("memory.limit_in_bytes")),
+            Version::V2 => Box::new(MaxFile::from(self.inner.as_ref().join("memory.max"))),
+        }
     }
 
-    pub fn failcnt(&self) -> io::Result<usize> {
-        let file = self.inner.as_ref().join("memory.failcnt");
-        let count = read_to_string(&file)?.trim().parse().unwrap();
-        Ok(count)
+    /// `memory.failcnt` in v1 or the `max` event in `memory.events` in v2.
+    ///
+    /// The count in v2 is relative to the count recorded by `Context::reset`.
+    pub fn failcnt(&self) -> Result<usize> {
+        match self.version {
+            Version::V1 => read_attr(&self.inner.as_ref().join("memory.failcnt")),
+            Version::V2 => {
+                let failcnt: usize =
+                    read_keyed_attr(&self.inner.as_ref().join("memory.events"), "max")?;
+                Ok(failcnt.saturating_sub(self.baseline.memory_failcnt))
+            }
+        }
     }
 
-    pub fn swappiness(&'a self) -> Box<dyn AttrFile<'a, usize, usize> + 'a> {
-        Box::new(self.inner.as_ref().join("memory.swappiness"))
+    /// Number of processes killed by the OOM killer
+    /// read from `memory.oom_control` in v1 or `memory.events` in v2.
+    pub fn oom_kill(&self) -> Result<usize> {
+        match self.version {
+            Version::V1 => {
+                read_keyed_attr(&self.inner.as_ref().join("memory.oom_control"), "oom_kill")
+            }
+            Version::V2 => read_keyed_attr(&self.inner.as_ref().join("memory.events"), "oom_kill"),
+        }
+    }
+
+    /// Return `true` if any process in the context has been killed by the OOM killer.
+    pub fn oom_killed(&self) -> Result<bool> {
+        Ok(self.oom_kill()? > 0)
+    }
+
+    /// `memory.memsw.limit_in_bytes` which is only available in v1,
+    /// the limit of memory and swap usage in total.
+    pub fn memsw_limit_in_bytes(&'a self) -> Result<Box<dyn AttrFile<'a, usize, usize> + 'a>> {
+        match self.version {
+            Version::V1 => Ok(Box::new(
+                self.inner.as_ref().join("memory.memsw.limit_in_bytes"),
+            )),
+            Version::V2 => Err(unsupported_in_v2("memory.memsw.limit_in_bytes")),
+        }
+    }
+
+    /// `memory.swap.max` which is only available in v2,
+    /// the limit of swap usage.
+    pub fn swap_max(&'a self) -> Result<Box<dyn AttrFile<'a, usize, usize> + 'a>> {
+        match self.version {
+            Version::V1 => Err(unsupported_in_v1("memory.swap.max")),
+            Version::V2 => Ok(Box::new(MaxFile::from(
+                self.inner.as_ref().join("memory.swap.max"),
+            ))),
+        }
+    }
+
+    /// Limit the memory and swap usage in total to `limit` bytes.
+    ///
+    /// The memory usage is also limited to `limit` bytes.
+    /// Swap is disabled in v2, which can not limit the total usage.
+    /// If the kernel does not account swap, swap is disabled
+    /// by setting `memory.swappiness` to 0 in v1,
+    /// and an `Err` is returned in v2.
+    pub fn limit_memsw(&self, limit: usize) -> Result<()> {
+        let dir = self.inner.as_ref();
+        match self.version {
+            Version::V1 => {
+                let mut limit_file = dir.join("memory.limit_in_bytes");
+                let memsw_file = dir.join("memory.memsw.limit_in_bytes");
+                if !memsw_file.exists() {
+                    AttrFile::<usize, usize>::write(&mut limit_file, &limit)?;
+                    return AttrFile::<usize, usize>::write(&mut dir.join("memory.swappiness"), &0);
+                }
+                // The memsw limit can not be less than the memory limit
+                let current: usize = AttrFile::<usize, usize>::read(&limit_file)?;
+                let mut files = [limit_file, memsw_file];
+                if limit > current {
+                    files.reverse();
+                }
+                for file in files.iter_mut() {
+                    AttrFile::<usize, usize>::write(file, &limit)?;
+                }
+                Ok(())
+            }
+            Version::V2 => {
+                MaxFile::from(dir.join("memory.max")).write(&limit)?;
+                let swap_file = dir.join("memory.swap.max");
+                if !swap_file.exists() {
+                    return Err(Error::Cgroup(String::from(
+                        "Swap can not be limited without memory.swap.max",
+                    )));
+                }
+                MaxFile::from(swap_file).write(&0)
+            }
+        }
+    }
+
+    /// Reset `memory.max_usage_in_bytes` to the current usage and `memory.failcnt` to zero.
+    ///
+    /// Only supported by v1, and the memsw counters are reset as well.
+    /// See `Context::reset` for v2.
+    /// Pages charged to the context, such as page cache, are reclaimed first
+    /// by `memory.force_empty`, which requires no process in the context.
+    pub fn reset(&self) -> Result<()> {
+        if self.version == Version::V2 {
+            return Err(unsupported_in_v2("Resetting memory usage"));
+        }
+        let dir = self.inner.as_ref();
+        write(dir.join("memory.force_empty"), "0")?;
+        for attr in &[
+            "memory.max_usage_in_bytes",
+            "memory.failcnt",
+            "memory.memsw.max_usage_in_bytes",
+            "memory.memsw.failcnt",
+        ] {
+            let file = dir.join(attr);
+            if file.exists() {
+                write(&file, "0")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `memory.swappiness` which is only available in v1.
+    pub fn swappiness(&'a self) -> Result<Box<dyn AttrFile<'a, usize, usize> + 'a>> {
+        match self.version {
+            Version::V1 => Ok(Box::new(self.inner.as_ref().join("memory.swappiness"))),
+            Version::V2 => Err(unsupported_in_v2("memory.swappiness")),
+        }
     }
 }
 
 impl<'a> Controller<'a> for MemoryController<'a, PathBuf> {
     const NAME: &'static str = "memory";
 
-    fn from_ctx(context: &Context) -> MemoryController<PathBuf> {
+    fn from_ctx(context: &Context) -> MemoryController<'_, PathBuf> {
         MemoryController {
-            inner: Context::root().join(Self::NAME).join(&context.name),
+            inner: context.path(Self::NAME),
+            version: context.version,
+            baseline: &context.baseline,
             _mark: PhantomData,
         }
     }
 
-    fn initialize(&self) -> io::Result<()> {
-        match create_dir(&self.inner) {
-            Ok(_) => {}
-            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {}
-            Err(e) => return Err(e),
-        }
-        Ok(())
+    fn initialize(&self) -> Result<()> {
+        create_hierarchy(&self.inner, self.version, Some(Self::NAME))
     }
 }
 
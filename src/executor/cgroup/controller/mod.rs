@@ -1,12 +1,18 @@
+mod blkio;
 mod cpu;
 mod cpuacct;
+mod cpuset;
 mod memory;
+mod pids;
 
+use std::fs::{create_dir, read_to_string, write};
 use std::io;
+use std::path::Path;
 
+use super::attr_file::{parse_attr, read_attr, read_keyed_attr, ListFile, MaxFile};
 use super::*;
 
-pub use self::{cpu::*, cpuacct::*, memory::*};
+pub use self::{blkio::*, cpu::*, cpuacct::*, cpuset::*, memory::*, pids::*};
 
 /// Cgroup controller trait.
 ///
@@ -27,5 +33,38 @@ pub(super) trait Controller<'a> {
     ///
     /// The controller will be destroyed by the context
     /// when the context is dropped.
-    fn initialize(&self) -> io::Result<()>;
+    fn initialize(&self) -> Result<()>;
+}
+
+/// Create the directory of a controller if it does not exist.
+///
+/// In cgroup v2, the controller named `name` is enabled in the parent
+/// so that its interface files are available in the directory.
+fn create_hierarchy(path: &Path, version: Version, name: Option<&str>) -> Result<()> {
+    if let (Version::V2, Some(name), Some(parent)) = (version, name, path.parent()) {
+        let subtree_control = parent.join("cgroup.subtree_control");
+        let enabled = read_to_string(&subtree_control)?;
+        if !enabled
+            .split_whitespace()
+            .any(|controller| controller == name)
+        {
+            write(&subtree_control, format!("+{}", name))?;
+        }
+    }
+    match create_dir(path) {
+        Ok(_) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+/// Error of the attribute which does not exist in cgroup v1.
+fn unsupported_in_v1(attr: &str) -> Error {
+    Error::Cgroup(format!("{} is not supported by cgroup v1", attr))
+}
+
+/// Error of the attribute which does not exist in cgroup v2.
+fn unsupported_in_v2(attr: &str) -> Error {
+    Error::Cgroup(format!("{} is not supported by cgroup v2", attr))
 }
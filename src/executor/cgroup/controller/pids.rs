@@ -0,0 +1,48 @@
+use std::marker::PhantomData;
+use std::path::Path;
+
+use super::*;
+
+/// Process number controller.
+pub struct PidsController<'a, T: 'a + AsRef<Path>> {
+    inner: T,
+    version: Version,
+    _mark: PhantomData<&'a ()>,
+}
+
+impl<'a, T: 'a + AsRef<Path>> PidsController<'a, T> {
+    /// `pids.max`, the maximum number of processes and threads.
+    ///
+    /// No limit is represented by `usize::MAX`.
+    pub fn max(&'a self) -> Box<dyn AttrFile<'a, usize, usize> + 'a> {
+        Box::new(MaxFile::from(self.inner.as_ref().join("pids.max")))
+    }
+
+    /// `pids.current`, the number of processes and threads.
+    pub fn current(&self) -> Result<usize> {
+        let file = self.inner.as_ref().join("pids.current");
+        read_attr(&file)
+    }
+}
+
+impl<'a> Controller<'a> for PidsController<'a, PathBuf> {
+    const NAME: &'static str = "pids";
+
+    fn from_ctx(context: &Context) -> PidsController<'_, PathBuf> {
+        PidsController {
+            inner: context.path(Self::NAME),
+            version: context.version,
+            _mark: PhantomData,
+        }
+    }
+
+    fn initialize(&self) -> Result<()> {
+        create_hierarchy(&self.inner, self.version, Some(Self::NAME))
+    }
+}
+
+impl<'a, T: 'a + AsRef<Path>> AsRef<Path> for PidsController<'a, T> {
+    fn as_ref(&self) -> &Path {
+        self.inner.as_ref()
+    }
+}
@@ -0,0 +1,71 @@
+//! Allocation of exclusive cpu cores for cgroup contexts.
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// A pool of cpu cores.
+///
+/// Cores allocated from the pool are not handed out again
+/// until they are returned, so concurrent runs never share a core.
+/// The pool can be cloned and all clones share the same cores.
+#[derive(Debug, Clone)]
+pub struct CorePool {
+    free: Arc<Mutex<Vec<usize>>>,
+}
+
+impl CorePool {
+    /// Create a pool of the given cores.
+    pub fn new<I: IntoIterator<Item = usize>>(cores: I) -> CorePool {
+        let mut free: Vec<usize> = cores.into_iter().collect();
+        free.sort_unstable();
+        free.dedup();
+        CorePool {
+            free: Arc::new(Mutex::new(free)),
+        }
+    }
+
+    /// Allocate `n` cores from the pool.
+    ///
+    /// Return `None` if there are not enough free cores.
+    pub fn allocate(&self, n: usize) -> Option<Cores> {
+        let mut free = self.free.lock().unwrap_or_else(PoisonError::into_inner);
+        if n == 0 || free.len() < n {
+            return None;
+        }
+        let cores = free.drain(..n).collect();
+        Some(Cores {
+            cores,
+            free: Arc::clone(&self.free),
+        })
+    }
+
+    /// Return the number of free cores.
+    pub fn available(&self) -> usize {
+        self.free
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+}
+
+/// Cores allocated from a `CorePool`.
+///
+/// They are returned to the pool when dropped.
+#[derive(Debug)]
+pub struct Cores {
+    cores: Vec<usize>,
+    free: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Cores {
+    /// Indices of the allocated cores.
+    pub fn as_slice(&self) -> &[usize] {
+        &self.cores
+    }
+}
+
+impl Drop for Cores {
+    fn drop(&mut self) {
+        let mut free = self.free.lock().unwrap_or_else(PoisonError::into_inner);
+        free.append(&mut self.cores);
+        free.sort_unstable();
+    }
+}
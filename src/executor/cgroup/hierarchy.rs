@@ -1,10 +1,11 @@
 use std::fs::{read_to_string, write};
-use std::io;
 use std::path::Path;
 
 use nix::unistd::Pid;
 
+use super::attr_file::parse_attr;
 use super::AttrFile;
+use crate::error::Result;
 
 /// Hierarchy in the cgroup.
 pub trait Hierarchy<'a> {
@@ -47,21 +48,15 @@ impl<T: AsRef<Path>> From<T> for PidFile<T> {
 }
 
 impl<'a, T: AsRef<Path>> AttrFile<'a, Pid, Vec<Pid>> for PidFile<T> {
-    fn write(&mut self, pid: &Pid) -> io::Result<()> {
+    fn write(&mut self, pid: &Pid) -> Result<()> {
         write(&self.inner, pid.to_string())?;
         Ok(())
     }
 
-    fn read(&self) -> io::Result<Vec<Pid>> {
-        Ok(read_to_string(&self.inner)?
+    fn read(&self) -> Result<Vec<Pid>> {
+        read_to_string(&self.inner)?
             .split_whitespace()
-            .map(|pid| -> Pid {
-                Pid::from_raw(
-                    pid.trim()
-                        .parse()
-                        .expect("Failed to get pid from task file"),
-                )
-            })
-            .collect())
+            .map(|pid| Ok(Pid::from_raw(parse_attr(self.inner.as_ref(), pid)?)))
+            .collect()
     }
 }
@@ -1,39 +1,137 @@
 mod attr_file;
 mod controller;
+mod cores;
 mod hierarchy;
+mod pool;
 
-use std::fs::remove_dir;
-use std::io;
+use std::collections::HashMap;
+use std::fs::{remove_dir, File, OpenOptions};
+use std::io::{self, Write as _};
+use std::os::unix::io::AsRawFd;
 use std::os::unix::process::CommandExt as _;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::SystemTime;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use nix::unistd::Pid;
+use lazy_static::lazy_static;
+use nix::errno::Errno;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::{write, Pid};
 use rand;
 
+use super::io_error;
+use crate::error::{Error, Result};
+
 pub use attr_file::AttrFile;
 pub use controller::*;
+pub use cores::{CorePool, Cores};
 pub use hierarchy::*;
+pub use pool::{Pool, PoolGuard};
 
 const CGROUP_ROOT: &str = "/sys/fs/cgroup";
 
+lazy_static! {
+    /// Versions of the cgroup filesystems which have been probed.
+    static ref VERSIONS: Mutex<HashMap<PathBuf, Version>> = Mutex::new(HashMap::new());
+}
+
+/// Version of the cgroup filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// A hierarchy for each controller, such as `/sys/fs/cgroup/cpu`.
+    V1,
+    /// A unified hierarchy for all controllers.
+    V2,
+}
+
+impl Version {
+    /// Probe the version of the cgroup filesystem mounted on `root`.
+    ///
+    /// Only the unified hierarchy has `cgroup.controllers` in its root.
+    fn probe(root: &Path) -> Version {
+        if root.join("cgroup.controllers").is_file() {
+            Version::V2
+        } else {
+            Version::V1
+        }
+    }
+
+    /// Get the version of the cgroup filesystem mounted on `root`,
+    /// which is only probed once for each root.
+    fn of(root: &Path) -> Version {
+        *VERSIONS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(root.to_owned())
+            .or_insert_with(|| Version::probe(root))
+    }
+}
+
 /// Cgroup context.
-#[derive(Debug, Clone)]
+///
+/// The directories of the context are removed when it is dropped,
+/// so child contexts nested in it should be dropped before it.
+#[derive(Debug)]
 pub struct Context {
     name: String,
+    /// Mount point of the cgroup filesystem.
+    root: PathBuf,
+    /// Path of the parent in each hierarchy, relative to the hierarchy.
+    parent: PathBuf,
+    version: Version,
+    blkio_controller_enable: bool,
     cpu_controller_enable: bool,
     cpuacct_controller_enable: bool,
+    cpuset_controller_enable: bool,
     memory_controller_enable: bool,
+    pids_controller_enable: bool,
+    /// Cores allocated to the context, which are returned when it is dropped.
+    cores: Option<Cores>,
+    /// Usage recorded by the last reset in cgroup v2.
+    baseline: Baseline,
+}
+
+/// Usage of a context in cgroup v2 recorded when it is reset.
+///
+/// The counters of v2 can not be reset,
+/// so the usage is reported relative to the recorded one.
+#[derive(Debug, Default)]
+struct Baseline {
+    cpu_usage: Duration,
+    cpu_user: Duration,
+    cpu_system: Duration,
+    memory_failcnt: usize,
+    /// `memory.peak` reset by writing to it,
+    /// which only affects the reads through the same file.
+    memory_peak: Option<File>,
+    io_stat: Vec<(Device, IoStat)>,
 }
 
 impl Context {
+    /// Version of the cgroup filesystem used by the context.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Get the blkio controller.
+    ///
+    /// Return `None` if the controller has not been initialized.
+    pub fn blkio_controller(&self) -> Option<BlkioController<'_, PathBuf>> {
+        if self.blkio_controller_enable {
+            Some(BlkioController::from_ctx(self))
+        } else {
+            None
+        }
+    }
+
     /// Get the cpu controller.
     ///
     /// Return `None` if the controller has not been initialized.
-    pub fn cpu_controller(&self) -> Option<CpuController<PathBuf>> {
+    pub fn cpu_controller(&self) -> Option<CpuController<'_, PathBuf>> {
         if self.cpu_controller_enable {
-            Some(CpuController::from_ctx(&self))
+            Some(CpuController::from_ctx(self))
         } else {
             None
         }
@@ -42,27 +140,127 @@ impl Context {
     /// Get the cpuacct controller.
     ///
     /// Return `None` if the controller has not been initialized.
-    pub fn cpuacct_controller(&self) -> Option<CpuAcctController<PathBuf>> {
+    pub fn cpuacct_controller(&self) -> Option<CpuAcctController<'_, PathBuf>> {
         if self.cpuacct_controller_enable {
-            Some(CpuAcctController::from_ctx(&self))
+            Some(CpuAcctController::from_ctx(self))
         } else {
             None
         }
     }
 
-    /// Get the cpuacct controller.
+    /// Get the cpuset controller.
+    ///
+    /// Return `None` if the controller has not been initialized.
+    pub fn cpuset_controller(&self) -> Option<CpusetController<'_, PathBuf>> {
+        if self.cpuset_controller_enable {
+            Some(CpusetController::from_ctx(self))
+        } else {
+            None
+        }
+    }
+
+    /// Get the memory controller.
     ///
     /// Return `None` if the controller has not been initialized.
-    pub fn memory_controller(&self) -> Option<MemoryController<PathBuf>> {
+    pub fn memory_controller(&self) -> Option<MemoryController<'_, PathBuf>> {
         if self.memory_controller_enable {
-            Some(MemoryController::from_ctx(&self))
+            Some(MemoryController::from_ctx(self))
         } else {
             None
         }
     }
 
+    /// Get the pids controller.
+    ///
+    /// Return `None` if the controller has not been initialized.
+    pub fn pids_controller(&self) -> Option<PidsController<'_, PathBuf>> {
+        if self.pids_controller_enable {
+            Some(PidsController::from_ctx(self))
+        } else {
+            None
+        }
+    }
+
+    /// Reset the usage counters so that the context can be reused.
+    ///
+    /// Limits are kept. Fail if any process is still in the context
+    /// or the counters can not be reset, which happens after a process
+    /// has been killed by the OOM killer.
+    ///
+    /// In cgroup v2, the current usage is recorded and the later usage
+    /// is reported relative to it, except `memory.peak` which is reset
+    /// by writing to it. It fails if the kernel does not support that,
+    /// which requires Linux 6.12 or later.
+    pub fn reset(&mut self) -> Result<()> {
+        for hierarchy in self.hierarchies() {
+            if !hierarchy.procs().read()?.is_empty() {
+                return Err(Error::Cgroup(format!(
+                    "Processes remain in {}",
+                    hierarchy.path().display()
+                )));
+            }
+        }
+        if let Some(controller) = self.memory_controller() {
+            if controller.oom_killed()? {
+                return Err(Error::Cgroup(String::from(
+                    "The OOM kill counter can not be reset",
+                )));
+            }
+        }
+        if self.version == Version::V2 {
+            return self.record_baseline();
+        }
+        if let Some(controller) = self.memory_controller() {
+            controller.reset()?;
+        }
+        if let Some(controller) = self.cpuacct_controller() {
+            controller.reset()?;
+        }
+        if let Some(controller) = self.blkio_controller() {
+            controller.reset()?;
+        }
+        Ok(())
+    }
+
+    /// Record the current usage in cgroup v2 as the baseline.
+    fn record_baseline(&mut self) -> Result<()> {
+        // Controllers report the usage relative to the baseline
+        self.baseline = Baseline::default();
+        let mut baseline = Baseline::default();
+        if let Some(controller) = self.memory_controller() {
+            baseline.memory_failcnt = controller.failcnt()?;
+            let mut peak = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .truncate(true)
+                .open(controller.as_ref().join("memory.peak"))?;
+            peak.write_all(b"0")?;
+            baseline.memory_peak = Some(peak);
+        }
+        if let Some(controller) = self.cpuacct_controller() {
+            baseline.cpu_usage = controller.usage()?;
+            baseline.cpu_user = controller.usage_user()?;
+            baseline.cpu_system = controller.usage_sys()?;
+        }
+        if let Some(controller) = self.blkio_controller() {
+            baseline.io_stat = controller.stat()?;
+        }
+        self.baseline = baseline;
+        Ok(())
+    }
+
+    /// Kill all processes in the context.
+    pub fn kill_all(&self) -> Result<()> {
+        for hierarchy in self.hierarchies() {
+            for pid in hierarchy.procs().read()? {
+                let _ = kill(pid, Signal::SIGKILL);
+            }
+        }
+        Ok(())
+    }
+
     /// Add a process to the context.
-    pub fn add_process(&mut self, pid: Pid) -> io::Result<()> {
+    pub fn add_process(&mut self, pid: Pid) -> Result<()> {
         for hierarchy in self.hierarchies() {
             hierarchy.procs().write(&pid)?;
         }
@@ -70,7 +268,15 @@ impl Context {
     }
 
     /// Add a task(thread) to the context.
-    pub fn add_task(&mut self, pid: Pid) -> io::Result<()> {
+    ///
+    /// Only supported by cgroup v1,
+    /// since threads of a process can not be in different cgroups in v2.
+    pub fn add_task(&mut self, pid: Pid) -> Result<()> {
+        if self.version == Version::V2 {
+            return Err(Error::Cgroup(String::from(
+                "Adding a single task is not supported by cgroup v2",
+            )));
+        }
         for hierarchy in self.hierarchies() {
             hierarchy.tasks().write(&pid)?;
         }
@@ -79,31 +285,87 @@ impl Context {
 }
 
 impl Context {
-    /// Root path of the cgroup filesystem.
-    fn root() -> &'static Path {
-        Path::new(CGROUP_ROOT)
+    /// Path of the context in the hierarchy of the controller.
+    ///
+    /// All controllers share the same path in cgroup v2.
+    fn path(&self, controller: &str) -> PathBuf {
+        let hierarchy = match self.version {
+            Version::V1 => self.root.join(controller),
+            Version::V2 => self.root.clone(),
+        };
+        hierarchy.join(&self.parent).join(&self.name)
+    }
+
+    /// Names of the enabled controllers.
+    fn controllers(&self) -> Vec<&'static str> {
+        [
+            ("blkio", self.blkio_controller_enable),
+            ("cpu", self.cpu_controller_enable),
+            ("cpuacct", self.cpuacct_controller_enable),
+            ("cpuset", self.cpuset_controller_enable),
+            ("memory", self.memory_controller_enable),
+            ("pids", self.pids_controller_enable),
+        ]
+        .iter()
+        .filter(|(_, enable)| *enable)
+        .map(|(controller, _)| *controller)
+        .collect()
     }
 
     /// All hierarchies that this cgroup context contains.
-    fn hierarchies<'a>(&'a self) -> Vec<Box<dyn 'a + Hierarchy>> {
+    fn hierarchies<'a>(&'a self) -> Vec<Box<dyn 'a + Hierarchy<'a>>> {
         let mut res: Vec<Box<dyn Hierarchy>> = Vec::new();
+        if self.version == Version::V2 {
+            if self.blkio_controller_enable
+                || self.cpu_controller_enable
+                || self.cpuacct_controller_enable
+                || self.cpuset_controller_enable
+                || self.memory_controller_enable
+                || self.pids_controller_enable
+            {
+                res.push(Box::new(self.path("")));
+            }
+            return res;
+        }
+        if let Some(controller) = self.blkio_controller() {
+            res.push(Box::new(controller));
+        }
         if let Some(controller) = self.cpu_controller() {
             res.push(Box::new(controller));
         }
         if let Some(controller) = self.cpuacct_controller() {
             res.push(Box::new(controller));
         }
+        if let Some(controller) = self.cpuset_controller() {
+            res.push(Box::new(controller));
+        }
         if let Some(controller) = self.memory_controller() {
             res.push(Box::new(controller));
         }
+        if let Some(controller) = self.pids_controller() {
+            res.push(Box::new(controller));
+        }
         res
     }
 }
 
 impl Drop for Context {
     fn drop(&mut self) {
+        let deadline = Instant::now() + Duration::from_secs(1);
         for hierarchy in self.hierarchies() {
-            let _ = remove_dir(hierarchy.path());
+            // Processes left behind, such as orphaned children of the program,
+            // keep the directory busy until they are killed and reaped.
+            while remove_dir(hierarchy.path()).is_err() && Instant::now() < deadline {
+                match hierarchy.procs().read() {
+                    Ok(pids) => {
+                        for pid in pids {
+                            let _ = kill(pid, Signal::SIGKILL);
+                        }
+                    }
+                    Err(_) => break,
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
         }
     }
 }
@@ -111,9 +373,20 @@ impl Drop for Context {
 /// Cgroup context builder.
 pub struct Builder {
     name: Option<String>,
+    root: PathBuf,
+    parent: PathBuf,
+    blkio_controller: bool,
     cpu_controller: bool,
     cpuacct_controller: bool,
+    cpuset_controller: bool,
     memory_controller: bool,
+    pids_controller: bool,
+    cores: Option<Cores>,
+    memsw_limit: Option<usize>,
+    /// Version of the parent context.
+    version: Option<Version>,
+    /// Controllers enabled by the parent context.
+    parent_controllers: Option<Vec<&'static str>>,
 }
 
 impl Builder {
@@ -126,6 +399,45 @@ impl Builder {
         self
     }
 
+    /// Set the mount point of the cgroup filesystem, "/sys/fs/cgroup" by default.
+    ///
+    /// The version of the cgroup filesystem is probed from it.
+    pub fn root<P: AsRef<Path>>(mut self, root: P) -> Builder {
+        self.root = root.as_ref().to_owned();
+        self.version = None;
+        self.parent_controllers = None;
+        self
+    }
+
+    /// Create the context under `parent` in each hierarchy,
+    /// such as a delegated "system.slice/judge.service".
+    ///
+    /// The path is relative to the hierarchy,
+    /// and the controllers of the context should be available in it.
+    pub fn parent_path<P: AsRef<Path>>(mut self, parent: P) -> Builder {
+        let parent = parent.as_ref();
+        self.parent = parent.strip_prefix("/").unwrap_or(parent).to_owned();
+        self
+    }
+
+    /// Create the context nested in `parent`,
+    /// whose limits apply to all its child contexts in total.
+    ///
+    /// Only the controllers enabled by the parent can be enabled,
+    /// otherwise building the context returns an `Err`.
+    pub fn parent(mut self, parent: &Context) -> Builder {
+        self.root = parent.root.clone();
+        self.parent = parent.parent.join(&parent.name);
+        self.version = Some(parent.version);
+        self.parent_controllers = Some(parent.controllers());
+        self
+    }
+
+    pub fn blkio_controller(mut self, flag: bool) -> Builder {
+        self.blkio_controller = flag;
+        self
+    }
+
     pub fn cpu_controller(mut self, flag: bool) -> Builder {
         self.cpu_controller = flag;
         self
@@ -136,31 +448,94 @@ impl Builder {
         self
     }
 
+    pub fn cpuset_controller(mut self, flag: bool) -> Builder {
+        self.cpuset_controller = flag;
+        self
+    }
+
+    /// Pin the context to the cores allocated from a `CorePool`.
+    ///
+    /// It enables the cpuset controller.
+    pub fn cores(mut self, cores: Cores) -> Builder {
+        self.cpuset_controller = true;
+        self.cores = Some(cores);
+        self
+    }
+
     pub fn memory_controller(mut self, flag: bool) -> Builder {
         self.memory_controller = flag;
         self
     }
 
-    pub fn build(self) -> io::Result<Context> {
+    /// Limit the memory and swap usage in total to `limit` bytes.
+    ///
+    /// It enables the memory controller.
+    /// See `MemoryController::limit_memsw` for details.
+    pub fn memsw_limit(mut self, limit: usize) -> Builder {
+        self.memory_controller = true;
+        self.memsw_limit = Some(limit);
+        self
+    }
+
+    pub fn pids_controller(mut self, flag: bool) -> Builder {
+        self.pids_controller = flag;
+        self
+    }
+
+    pub fn build(self) -> Result<Context> {
+        if let Some(parent_controllers) = &self.parent_controllers {
+            let controllers = [
+                ("blkio", self.blkio_controller),
+                ("cpu", self.cpu_controller),
+                ("cpuacct", self.cpuacct_controller),
+                ("cpuset", self.cpuset_controller),
+                ("memory", self.memory_controller),
+                ("pids", self.pids_controller),
+            ];
+            for (controller, _) in controllers.iter().filter(|(_, enable)| *enable) {
+                if !parent_controllers.contains(controller) {
+                    return Err(Error::Config(format!(
+                        "The {} controller is not enabled by the parent context",
+                        controller
+                    )));
+                }
+            }
+        }
+
+        let root = &self.root;
+        let version = self.version.unwrap_or_else(|| Version::of(root));
         let name = match self.name {
             Some(name) => name,
             None => {
                 let timestamp = SystemTime::now()
                     .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap()
-                    .as_nanos();
+                    .map(|timestamp| timestamp.as_nanos())
+                    .unwrap_or_default();
                 let salt: u128 = rand::random();
                 format!("{:x}{:x}", timestamp, salt)
             }
         };
 
-        let ctx = Context {
+        let mut ctx = Context {
             name,
+            version,
+            root: self.root,
+            parent: self.parent,
+            blkio_controller_enable: self.blkio_controller,
             cpu_controller_enable: self.cpu_controller,
             cpuacct_controller_enable: self.cpuacct_controller,
+            cpuset_controller_enable: self.cpuset_controller,
             memory_controller_enable: self.memory_controller,
+            pids_controller_enable: self.pids_controller,
+            cores: None,
+            baseline: Baseline::default(),
         };
 
+        if self.blkio_controller {
+            let controller = BlkioController::from_ctx(&ctx);
+            controller.initialize()?
+        }
+
         if self.cpu_controller {
             let controller = CpuController::from_ctx(&ctx);
             controller.initialize()?
@@ -171,8 +546,25 @@ impl Builder {
             controller.initialize()?
         }
 
+        if self.cpuset_controller {
+            let controller = CpusetController::from_ctx(&ctx);
+            controller.initialize()?;
+            if let Some(cores) = &self.cores {
+                controller.cpus().write(&cores.as_slice().to_vec())?;
+            }
+        }
+        ctx.cores = self.cores;
+
         if self.memory_controller {
             let controller = MemoryController::from_ctx(&ctx);
+            controller.initialize()?;
+            if let Some(limit) = self.memsw_limit {
+                controller.limit_memsw(limit)?;
+            }
+        }
+
+        if self.pids_controller {
+            let controller = PidsController::from_ctx(&ctx);
             controller.initialize()?
         }
 
@@ -184,25 +576,53 @@ impl Default for Builder {
     fn default() -> Builder {
         Builder {
             name: None,
+            root: PathBuf::from(CGROUP_ROOT),
+            parent: PathBuf::new(),
+            blkio_controller: false,
             cpu_controller: true,
             cpuacct_controller: true,
+            cpuset_controller: false,
             memory_controller: true,
+            pids_controller: false,
+            cores: None,
+            memsw_limit: None,
+            version: None,
+            parent_controllers: None,
         }
     }
 }
 
 pub trait CommandExt {
     /// Attach the child process to the cgroup.
-    fn cgroup(&mut self, ctx: Context) -> &mut Command;
+    ///
+    /// The context is still owned by the caller
+    /// and should be kept until the child process exits.
+    fn cgroup(&mut self, ctx: &Context) -> &mut Command;
 }
 
 impl CommandExt for Command {
-    fn cgroup(&mut self, ctx: Context) -> &mut Command {
-        // Ensure that the cgroup context will not be dropped in child process
-        let ctx = Box::leak(Box::new(ctx));
+    fn cgroup(&mut self, ctx: &Context) -> &mut Command {
+        // Open the `cgroup.procs` files in the parent process,
+        // so that the child process only needs to write "0" to them
+        // which moves itself into the cgroup without any allocation.
+        let files: std::result::Result<Vec<File>, i32> = ctx
+            .hierarchies()
+            .iter()
+            .map(|hierarchy| {
+                OpenOptions::new()
+                    .write(true)
+                    .open(hierarchy.path().join("cgroup.procs"))
+                    .map_err(|e| e.raw_os_error().unwrap_or(Errno::EINVAL as i32))
+            })
+            .collect();
         unsafe {
             self.pre_exec(move || {
-                ctx.add_process(nix::unistd::Pid::this())?;
+                let files = files
+                    .as_ref()
+                    .map_err(|&e| io::Error::from_raw_os_error(e))?;
+                for file in files {
+                    write(file.as_raw_fd(), b"0").map_err(io_error)?;
+                }
                 Ok(())
             });
         }
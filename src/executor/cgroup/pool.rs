@@ -0,0 +1,153 @@
+//! Reusable cgroup contexts.
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+use super::{Builder, Context};
+use crate::error::Result;
+
+/// A pool of cgroup contexts which are created in advance
+/// and reused to avoid creating and removing directories for every run.
+///
+/// Usage counters of a context are reset by `Context::reset`
+/// when it is returned to the pool.
+/// If it can not be reset, it is removed and a new one is built when needed.
+pub struct Pool {
+    state: Mutex<State>,
+    returned: Condvar,
+    builder: Box<dyn Fn() -> Builder + Send + Sync>,
+}
+
+struct State {
+    idle: Vec<Context>,
+    /// Number of contexts removed from the pool to be built again.
+    missing: usize,
+}
+
+/// A context taken from the state of a pool.
+enum Taken {
+    Idle(Context),
+    /// The context has been removed and should be built again.
+    Missing,
+}
+
+impl State {
+    fn take(&mut self) -> Option<Taken> {
+        if let Some(ctx) = self.idle.pop() {
+            return Some(Taken::Idle(ctx));
+        }
+        if self.missing > 0 {
+            self.missing -= 1;
+            return Some(Taken::Missing);
+        }
+        None
+    }
+}
+
+impl Pool {
+    /// Create a pool of `size` contexts built by the builders from `builder`.
+    ///
+    /// The builders should not set the name of the context.
+    pub fn new<F>(size: usize, builder: F) -> Result<Pool>
+    where
+        F: 'static + Fn() -> Builder + Send + Sync,
+    {
+        let idle: Vec<Context> = (0..size)
+            .map(|_| builder().build())
+            .collect::<Result<_>>()?;
+        Ok(Pool {
+            state: Mutex::new(State { idle, missing: 0 }),
+            returned: Condvar::new(),
+            builder: Box::new(builder),
+        })
+    }
+
+    /// Take a context from the pool.
+    ///
+    /// Block until a context is returned if all of them are in use.
+    pub fn get(&self) -> Result<PoolGuard<'_>> {
+        let mut state = self.lock();
+        loop {
+            if let Some(taken) = state.take() {
+                drop(state);
+                return self.guard(taken);
+            }
+            state = self
+                .returned
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Take a context from the pool if any of them is not in use.
+    pub fn try_get(&self) -> Result<Option<PoolGuard<'_>>> {
+        let taken = self.lock().take();
+        taken.map(|taken| self.guard(taken)).transpose()
+    }
+
+    /// Return the number of contexts which are not in use.
+    pub fn idle(&self) -> usize {
+        let state = self.lock();
+        state.idle.len() + state.missing
+    }
+
+    /// Wrap the taken context, building a new one if it is missing.
+    ///
+    /// It is called without holding the lock since building a context is slow.
+    fn guard(&self, taken: Taken) -> Result<PoolGuard<'_>> {
+        let ctx = match taken {
+            Taken::Idle(ctx) => ctx,
+            Taken::Missing => match (self.builder)().build() {
+                Ok(ctx) => ctx,
+                Err(e) => {
+                    self.lock().missing += 1;
+                    self.returned.notify_one();
+                    return Err(e);
+                }
+            },
+        };
+        Ok(PoolGuard {
+            pool: self,
+            ctx: Some(ctx),
+        })
+    }
+
+    fn put(&self, mut ctx: Context) {
+        let reset = ctx.reset();
+        let mut state = self.lock();
+        match reset {
+            Ok(()) => state.idle.push(ctx),
+            Err(_) => state.missing += 1,
+        }
+        self.returned.notify_one();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The state is always consistent, so it is fine to use it
+        // even if another thread panicked.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A context taken from a `Pool`.
+///
+/// It is returned to the pool when dropped.
+pub struct PoolGuard<'a> {
+    pool: &'a Pool,
+    ctx: Option<Context>,
+}
+
+impl<'a> Deref for PoolGuard<'a> {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        self.ctx.as_ref().unwrap()
+    }
+}
+
+impl<'a> Drop for PoolGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(ctx) = self.ctx.take() {
+            self.pool.put(ctx);
+        }
+    }
+}
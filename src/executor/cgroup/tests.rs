@@ -1,14 +1,22 @@
 use super::*;
 
 use std::iter::FromIterator;
+use std::process::Stdio;
 use std::time::Duration;
 
 #[test]
 fn test_cgroup_path() -> io::Result<()> {
-    let ctx = Builder::new().build()?;
+    let ctx = Builder::new().pids_controller(true).build()?;
     let cpu_controller = ctx.cpu_controller().unwrap();
     let cpu_path = cpu_controller.as_ref();
     assert!(cpu_path.exists());
+    if ctx.version() == Version::V2 {
+        assert_eq!(
+            cpu_path,
+            PathBuf::from_iter(&["/sys/fs/cgroup/", ctx.name.as_str()])
+        );
+        return Ok(());
+    }
     assert_eq!(
         cpu_path,
         PathBuf::from_iter(&["/sys/fs/cgroup/cpu/", ctx.name.as_str()])
@@ -30,6 +38,14 @@ fn test_cgroup_path() -> io::Result<()> {
         PathBuf::from_iter(&["/sys/fs/cgroup/memory/", ctx.name.as_str()])
     );
 
+    let pids_controller = ctx.pids_controller().unwrap();
+    let pids_path = pids_controller.as_ref();
+    assert!(pids_path.exists());
+    assert_eq!(
+        pids_path,
+        PathBuf::from_iter(&["/sys/fs/cgroup/pids/", ctx.name.as_str()])
+    );
+
     Ok(())
 }
 
@@ -78,6 +94,493 @@ fn test_memory_controller() -> io::Result<()> {
     assert_eq!(memory_controller.usage_in_bytes()?, 0);
     assert_eq!(memory_controller.max_usage_in_bytes()?, 0);
     assert_eq!(memory_controller.failcnt()?, 0);
+    assert_eq!(memory_controller.oom_kill()?, 0);
+
+    memory_controller.limit_in_bytes().write(&usize::MAX)?;
+    assert!(memory_controller.limit_in_bytes().read()? >= 1 << 40);
+
+    Ok(())
+}
+
+#[test]
+fn test_memsw_limit() -> io::Result<()> {
+    let ctx = Builder::new().memsw_limit(256 * 1024 * 1024).build()?;
+    let memory_controller = ctx.memory_controller().unwrap();
+    // Swap is only limited if the kernel accounts it
+    let swap_accounted = memory_controller
+        .as_ref()
+        .join("memory.memsw.limit_in_bytes")
+        .exists();
+
+    // Both decreasing and increasing the limit work
+    for &limit in &[256 * 1024 * 1024, 128 * 1024 * 1024, 512 * 1024 * 1024] {
+        memory_controller.limit_memsw(limit)?;
+        assert_eq!(memory_controller.limit_in_bytes().read()?, limit);
+        match ctx.version() {
+            Version::V1 if swap_accounted => {
+                assert_eq!(memory_controller.memsw_limit_in_bytes()?.read()?, limit)
+            }
+            Version::V1 => assert_eq!(memory_controller.swappiness()?.read()?, 0),
+            Version::V2 => assert_eq!(memory_controller.swap_max()?.read()?, 0),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_memsw_limit_without_swap_account() -> io::Result<()> {
+    let root = tempfile::tempdir()?;
+    std::fs::create_dir(root.path().join("memory"))?;
+    let ctx = Builder::new()
+        .root(root.path())
+        .cpu_controller(false)
+        .cpuacct_controller(false)
+        .memsw_limit(256 * 1024 * 1024)
+        .build()?;
+    let memory_controller = ctx.memory_controller().unwrap();
+    assert_eq!(
+        memory_controller.limit_in_bytes().read()?,
+        256 * 1024 * 1024
+    );
+    assert_eq!(memory_controller.swappiness()?.read()?, 0);
+
+    let root = tempfile::tempdir()?;
+    std::fs::write(root.path().join("cgroup.controllers"), "memory")?;
+    std::fs::write(root.path().join("cgroup.subtree_control"), "")?;
+    let result = Builder::new()
+        .root(root.path())
+        .cpu_controller(false)
+        .cpuacct_controller(false)
+        .memsw_limit(256 * 1024 * 1024)
+        .build();
+    assert!(matches!(result, Err(Error::Cgroup(_))));
+    Ok(())
+}
+
+#[test]
+fn test_oom_killed() -> io::Result<()> {
+    let ctx = Builder::new().build()?;
+    let memory_controller = ctx.memory_controller().unwrap();
+    memory_controller
+        .limit_in_bytes()
+        .write(&(16 * 1024 * 1024))?;
+
+    let status = Command::new("sh")
+        .args(["-c", "x=$(head -c 64M /dev/zero | tr '\\0' a)"])
+        .cgroup(&ctx)
+        .status()?;
+    assert!(!status.success());
+    assert!(memory_controller.oom_killed()?);
+
+    Ok(())
+}
+
+#[test]
+fn test_blkio_controller() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let device = Device::of(dir.path())?;
+    // Filesystems without a block device such as tmpfs and overlay can not be throttled
+    if device.major == 0 {
+        eprintln!(
+            "Skip the test: {} is not on a block device",
+            dir.path().display()
+        );
+        return Ok(());
+    }
+    let ctx = Builder::new().blkio_controller(true).build()?;
+    let blkio_controller = ctx.blkio_controller().unwrap();
+
+    // Only whole disks can be throttled, partitions are rejected
+    match blkio_controller
+        .write_bps_device()
+        .write(&(device, 16 * 1024 * 1024))
+    {
+        Err(Error::Io(ref e))
+            if [Some(Errno::EINVAL as i32), Some(Errno::ENODEV as i32)]
+                .contains(&e.raw_os_error()) =>
+        {
+            eprintln!("Skip the test: device {} can not be throttled", device);
+            return Ok(());
+        }
+        result => result?,
+    }
+    blkio_controller.read_iops_device().write(&(device, 1000))?;
+    assert_eq!(
+        blkio_controller.write_bps_device().read()?,
+        vec![(device, 16 * 1024 * 1024)]
+    );
+    assert_eq!(
+        blkio_controller.read_iops_device().read()?,
+        vec![(device, 1000)]
+    );
+    blkio_controller.read_iops_device().write(&(device, 0))?;
+    assert_eq!(blkio_controller.read_iops_device().read()?, vec![]);
+
+    let direct_write = || {
+        let mut command = Command::new("dd");
+        command
+            .arg("if=/dev/zero")
+            .arg(format!("of={}", dir.path().join("zero").display()))
+            .args(["bs=64k", "count=16", "oflag=direct", "status=none"]);
+        command
+    };
+    // Direct IO is needed to bypass the page cache, but not all filesystems support it
+    if !direct_write().stderr(Stdio::null()).status()?.success() {
+        eprintln!(
+            "Skip the test: {} does not support direct IO",
+            dir.path().display()
+        );
+        return Ok(());
+    }
+    let status = direct_write().cgroup(&ctx).status()?;
+    assert!(status.success());
+    let written: u64 = blkio_controller
+        .stat()?
+        .iter()
+        .map(|(_, stat)| stat.write_bytes)
+        .sum();
+    assert!(written >= 1024 * 1024);
+
+    Ok(())
+}
+
+#[test]
+fn test_pids_controller() -> io::Result<()> {
+    let ctx = Builder::new().pids_controller(true).build()?;
+
+    let pids_controller = ctx.pids_controller().unwrap();
+    assert_eq!(pids_controller.max().read()?, usize::MAX);
+
+    pids_controller.max().write(&16)?;
+    assert_eq!(pids_controller.max().read()?, 16);
+    assert_eq!(pids_controller.current()?, 0);
+
+    pids_controller.max().write(&usize::MAX)?;
+    assert_eq!(pids_controller.max().read()?, usize::MAX);
+
+    Ok(())
+}
+
+#[test]
+fn test_cpuset_controller() -> io::Result<()> {
+    let ctx = Builder::new().cpuset_controller(true).build()?;
+
+    let cpuset_controller = ctx.cpuset_controller().unwrap();
+    let cpus = cpuset_controller.cpus().read()?;
+    assert!(!cpus.is_empty());
+    assert!(!cpuset_controller.mems().read()?.is_empty());
+
+    cpuset_controller.cpus().write(&vec![cpus[0]])?;
+    assert_eq!(cpuset_controller.cpus().read()?, vec![cpus[0]]);
+    assert_eq!(cpuset_controller.effective_cpus()?, vec![cpus[0]]);
+
+    Ok(())
+}
+
+#[test]
+fn test_core_pool() -> io::Result<()> {
+    let pool = CorePool::new(vec![3, 1, 2, 1]);
+    assert_eq!(pool.available(), 3);
+
+    let cores = pool.allocate(2).unwrap();
+    assert_eq!(cores.as_slice(), &[1, 2]);
+    assert!(pool.allocate(2).is_none());
+    assert_eq!(pool.clone().allocate(1).unwrap().as_slice(), &[3]);
+    drop(cores);
+    assert_eq!(pool.available(), 3);
+
+    let pool = CorePool::new(vec![0]);
+    let ctx = Builder::new().cores(pool.allocate(1).unwrap()).build()?;
+    assert_eq!(ctx.cpuset_controller().unwrap().cpus().read()?, vec![0]);
+    assert_eq!(pool.available(), 0);
+    drop(ctx);
+    assert_eq!(pool.available(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_pool() -> io::Result<()> {
+    let pool = Pool::new(2, Builder::new)?;
+    let first = pool.get()?;
+    let second = pool.try_get()?.unwrap();
+    assert_ne!(first.name, second.name);
+    assert!(pool.try_get()?.is_none());
+    drop(second);
+    assert_eq!(pool.idle(), 1);
+
+    let name = first.name.clone();
+    let status = Command::new("sh")
+        .args(["-c", "i=0; while [ $i -lt 10000 ]; do i=$((i+1)); done"])
+        .cgroup(&first)
+        .status()?;
+    assert!(status.success());
+    assert!(first.cpuacct_controller().unwrap().usage()? > Duration::from_secs(0));
+    drop(first);
+
+    let ctx = pool.get()?;
+    assert_eq!(ctx.name, name);
+    if ctx.version() == Version::V1 {
+        assert_eq!(
+            ctx.cpuacct_controller().unwrap().usage()?,
+            Duration::from_secs(0)
+        );
+        let memory_controller = ctx.memory_controller().unwrap();
+        assert_eq!(
+            memory_controller.max_usage_in_bytes()?,
+            memory_controller.usage_in_bytes()?
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_pool_rebuild() -> io::Result<()> {
+    let pool = Pool::new(1, Builder::new)?;
+    let ctx = pool.get()?;
+    let name = ctx.name.clone();
+    let mut child = Command::new("sleep").arg("10").cgroup(&ctx).spawn()?;
+    // A context with processes can not be reset, so it is built again
+    drop(ctx);
+    child.kill()?;
+    child.wait()?;
+    assert_eq!(pool.idle(), 1);
+    let ctx = pool.try_get()?.unwrap();
+    assert_ne!(ctx.name, name);
+    assert!(ctx.cpu_controller().unwrap().as_ref().exists());
+    Ok(())
+}
+
+/// Write fake interface files of a context in cgroup v2.
+fn write_v2_usage(path: &Path, usage_usec: u64, max_events: usize) -> io::Result<()> {
+    std::fs::write(path.join("cgroup.procs"), "")?;
+    std::fs::write(
+        path.join("cpu.stat"),
+        format!(
+            "usage_usec {}\nuser_usec {}\nsystem_usec 0\n",
+            usage_usec, usage_usec
+        ),
+    )?;
+    std::fs::write(
+        path.join("memory.events"),
+        format!("max {}\noom_kill 0\n", max_events),
+    )?;
+    std::fs::write(path.join("memory.peak"), "4096\n")
+}
+
+#[test]
+fn test_pool_v2() -> io::Result<()> {
+    let root = tempfile::tempdir()?;
+    std::fs::write(root.path().join("cgroup.controllers"), "cpu memory pids")?;
+    std::fs::write(root.path().join("cgroup.subtree_control"), "")?;
+    let root_path = root.path().to_owned();
+    let pool = Pool::new(1, move || Builder::new().root(&root_path))?;
+    let path = {
+        let ctx = pool.get()?;
+        assert_eq!(ctx.version(), Version::V2);
+        let path = ctx.cpuacct_controller().unwrap().as_ref().to_owned();
+        write_v2_usage(&path, 3000, 2)?;
+        assert_eq!(
+            ctx.cpuacct_controller().unwrap().usage()?,
+            Duration::from_millis(3)
+        );
+        path
+    };
+
+    // The usage is recorded when the context is returned
+    {
+        let ctx = pool.get()?;
+        let cpuacct_controller = ctx.cpuacct_controller().unwrap();
+        let memory_controller = ctx.memory_controller().unwrap();
+        assert_eq!(cpuacct_controller.usage()?, Duration::from_secs(0));
+        assert_eq!(memory_controller.failcnt()?, 0);
+        // `memory.peak` is reset by writing to it
+        assert_eq!(memory_controller.max_usage_in_bytes()?, 0);
+
+        write_v2_usage(&path, 5000, 3)?;
+        assert_eq!(cpuacct_controller.usage()?, Duration::from_millis(2));
+        assert_eq!(cpuacct_controller.usage_user()?, Duration::from_millis(2));
+        assert_eq!(memory_controller.failcnt()?, 1);
+    }
+    assert_eq!(pool.idle(), 1);
+    for file in &["cgroup.procs", "cpu.stat", "memory.events", "memory.peak"] {
+        std::fs::remove_file(path.join(file))?;
+    }
+    drop(pool);
+    assert!(!path.exists());
+    Ok(())
+}
+
+#[test]
+fn test_reset_v2_without_peak_reset() -> io::Result<()> {
+    let root = tempfile::tempdir()?;
+    std::fs::write(root.path().join("cgroup.controllers"), "cpu memory")?;
+    std::fs::write(root.path().join("cgroup.subtree_control"), "")?;
+    let mut ctx = Builder::new().root(root.path()).build()?;
+    let path = ctx.memory_controller().unwrap().as_ref().to_owned();
+    write_v2_usage(&path, 3000, 0)?;
+    // `memory.peak` is missing before Linux 5.19
+    std::fs::remove_file(path.join("memory.peak"))?;
+    assert!(ctx.reset().is_err());
+
+    for file in &["cgroup.procs", "cpu.stat", "memory.events"] {
+        std::fs::remove_file(path.join(file))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_context_drop() -> io::Result<()> {
+    let ctx = Builder::new().build()?;
+    let path = ctx.cpu_controller().unwrap().as_ref().to_owned();
+    let mut child = Command::new("sleep").arg("10").cgroup(&ctx).spawn()?;
+    let procs = ctx.cpu_controller().unwrap().procs().read()?;
+    assert_eq!(procs, vec![Pid::from_raw(child.id() as i32)]);
+
+    // Remaining processes are killed so that the directories can be removed
+    drop(ctx);
+    assert!(!path.exists());
+    assert!(!child.wait()?.success());
+    Ok(())
+}
+
+#[test]
+fn test_nested_context() -> io::Result<()> {
+    let parent = Builder::new().build()?;
+    parent
+        .memory_controller()
+        .unwrap()
+        .limit_in_bytes()
+        .write(&(16 * 1024 * 1024))?;
+
+    match Builder::new()
+        .parent(&parent)
+        .blkio_controller(true)
+        .build()
+    {
+        Err(Error::Config(_)) => {}
+        ctx => panic!("Unexpected context: {:?}", ctx),
+    }
+    let child = Builder::new().parent(&parent).build()?;
+    let child_path = child.memory_controller().unwrap().as_ref().to_owned();
+    assert_eq!(
+        child_path,
+        parent
+            .memory_controller()
+            .unwrap()
+            .as_ref()
+            .join(&child.name)
+    );
+    let same_child = Builder::new()
+        .parent_path(format!("/{}", parent.name))
+        .name(&child.name)
+        .build()?;
+    assert_eq!(same_child.memory_controller().unwrap().as_ref(), child_path);
+    // It shares the directories with `child` which removes them
+    std::mem::forget(same_child);
+
+    // The limit of the parent applies to its children
+    let status = Command::new("sh")
+        .args(["-c", "x=$(head -c 64M /dev/zero | tr '\\0' a)"])
+        .cgroup(&child)
+        .status()?;
+    assert!(!status.success());
+    assert!(child.memory_controller().unwrap().oom_killed()?);
+
+    drop(child);
+    assert!(!child_path.exists());
+    Ok(())
+}
+
+#[test]
+fn test_custom_root() -> io::Result<()> {
+    let root = tempfile::tempdir()?;
+    for controller in &["cpu", "cpuacct", "memory", "pids"] {
+        std::fs::create_dir_all(root.path().join(controller).join("judge"))?;
+    }
+    let ctx = Builder::new()
+        .root(root.path())
+        .parent_path("judge")
+        .pids_controller(true)
+        .build()?;
+    assert_eq!(ctx.version(), Version::V1);
+    let path = root.path().join("pids").join("judge").join(&ctx.name);
+    assert_eq!(ctx.pids_controller().unwrap().as_ref(), path);
+    assert!(path.exists());
+    drop(ctx);
+    assert!(!path.exists());
+
+    let root = tempfile::tempdir()?;
+    std::fs::write(root.path().join("cgroup.controllers"), "cpu memory pids")?;
+    std::fs::create_dir(root.path().join("judge"))?;
+    std::fs::write(root.path().join("judge").join("cgroup.subtree_control"), "")?;
+    let ctx = Builder::new()
+        .root(root.path())
+        .parent_path("judge")
+        .build()?;
+    assert_eq!(ctx.version(), Version::V2);
+    let path = root.path().join("judge").join(&ctx.name);
+    assert_eq!(ctx.memory_controller().unwrap().as_ref(), path);
+    assert_eq!(ctx.cpu_controller().unwrap().as_ref(), path);
+    assert!(path.exists());
+
+    // The version is probed only once for each root
+    std::fs::remove_file(root.path().join("cgroup.controllers"))?;
+    std::fs::write(path.join("cgroup.subtree_control"), "")?;
+    let child = Builder::new().parent(&ctx).build()?;
+    assert_eq!(child.version(), Version::V2);
+    let sibling = Builder::new()
+        .root(root.path())
+        .parent_path("judge")
+        .build()?;
+    assert_eq!(sibling.version(), Version::V2);
+    Ok(())
+}
+
+#[test]
+fn test_malformed_attr_file() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?.into_temp_path();
+    std::fs::write(&file, "not a number")?;
+    let attr = AttrFile::<usize, usize>::read(&file);
+    match attr {
+        Err(Error::Cgroup(_)) => {}
+        attr => panic!("Unexpected attribute: {:?}", attr),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_version_probe() -> io::Result<()> {
+    let root = tempfile::tempdir()?;
+    assert_eq!(Version::probe(root.path()), Version::V1);
+    std::fs::write(root.path().join("cgroup.controllers"), "cpu memory pids")?;
+    assert_eq!(Version::probe(root.path()), Version::V2);
+    Ok(())
+}
+
+#[test]
+fn test_v2_attr_files() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?.into_temp_path();
+
+    std::fs::write(&file, "max\n")?;
+    let mut max_file = attr_file::MaxFile::from(&file);
+    assert_eq!(max_file.read()?, usize::MAX);
+    max_file.write(&(128 * 1024))?;
+    assert_eq!(max_file.read()?, 128 * 1024);
+    max_file.write(&usize::MAX)?;
+    assert_eq!(std::fs::read_to_string(&file)?, "max");
+
+    std::fs::write(&file, "usage_usec 1200\nuser_usec 1000\nsystem_usec 200\n")?;
+    assert_eq!(attr_file::read_keyed_attr::<u64>(&file, "user_usec")?, 1000);
+    assert!(attr_file::read_keyed_attr::<u64>(&file, "nr_periods").is_err());
 
+    std::fs::write(&file, "0-3,6,8-9\n")?;
+    let mut list_file = attr_file::ListFile::from(&file);
+    assert_eq!(list_file.read()?, vec![0, 1, 2, 3, 6, 8, 9]);
+    list_file.write(&vec![1, 5])?;
+    assert_eq!(list_file.read()?, vec![1, 5]);
+    std::fs::write(&file, "\n")?;
+    assert_eq!(list_file.read()?, Vec::<usize>::new());
     Ok(())
 }
@@ -49,7 +49,7 @@ impl CommandExt for Command {
                         | nix::sched::CloneFlags::CLONE_NEWUTS
                         | nix::sched::CloneFlags::CLONE_SYSVSEM,
                 )
-                .expect("Failed to unshare namespace");
+                .map_err(io_error)?;
                 Ok(())
             });
         }
@@ -60,8 +60,8 @@ impl CommandExt for Command {
         let new_root = new_root.as_ref().to_owned();
         unsafe {
             self.pre_exec(move || {
-                nix::unistd::chroot(&new_root).expect("Failed to chroot to new path");
-                nix::unistd::chdir("/").expect("Failed to chdir to new root");
+                nix::unistd::chroot(&new_root).map_err(io_error)?;
+                nix::unistd::chdir("/").map_err(io_error)?;
                 Ok(())
             });
         }
@@ -69,6 +69,13 @@ impl CommandExt for Command {
     }
 }
 
+/// Convert a nix error into an io error without any allocation,
+/// so it is safe to be used in `pre_exec` closures.
+pub(crate) fn io_error(e: nix::Error) -> io::Error {
+    let errno = e.as_errno().unwrap_or(nix::errno::Errno::EINVAL);
+    io::Error::from_raw_os_error(errno as i32)
+}
+
 pub trait ChildExt {
     fn timeout(&mut self, timeout: Duration) -> io::Result<ExitStatus>;
     fn timeout_with_output(self, timeout: Duration) -> io::Result<Output>;
@@ -86,7 +93,7 @@ impl ChildExt for Child {
                     let _ = nix::sys::signal::kill(pid, nix::sys::signal::SIGKILL);
                 }
                 Err(mpsc::RecvTimeoutError::Disconnected) => {
-                    panic!("Channel disconnected unexpected")
+                    // Do nothing if waiting for the child process failed
                 }
             }
         });
@@ -106,7 +113,7 @@ impl ChildExt for Child {
                     let _ = nix::sys::signal::kill(pid, nix::sys::signal::SIGKILL);
                 }
                 Err(mpsc::RecvTimeoutError::Disconnected) => {
-                    panic!("Channel disconnected unexpected")
+                    // Do nothing if waiting for the child process failed
                 }
             }
         });
@@ -116,5 +123,8 @@ impl ChildExt for Child {
     }
 }
 
+// `test_chroot` calls `CommandExt::chroot` by method syntax,
+// which collides with an unstable method of the standard library
 #[cfg(test)]
+#[allow(unstable_name_collisions, clippy::needless_borrows_for_generic_args)]
 mod tests;
@@ -1,26 +1,57 @@
 //! Reduced high-level APIs for libseccomp.
 mod libseccomp;
 use libseccomp::*;
+mod policy;
+mod profiles;
+pub use policy::{Policy, PolicyRule};
+pub use profiles::profile_names;
 
 use std::ffi::CString;
+use std::io;
 use std::ops::Deref;
 use std::os::unix::process::CommandExt as _;
 use std::process::Command;
 
 use nix;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
 
 /// Syscall wrapper.
+///
+/// It holds the native number of the syscall, or the negative pseudo number
+/// of libseccomp if the syscall is not available on the native architecture.
 pub struct Syscall(u32);
 
 impl Syscall {
     /// Resolve the name of a syscall.
     ///
-    /// Panic if the argument is not a available syscall name.
-    pub fn from_name(name: &str) -> Syscall {
-        let name = CString::new(name).unwrap();
-        let syscall = unsafe { seccomp_syscall_resolve_name(name.as_ptr()) };
-        assert!(syscall >= 0, "No such syscall");
-        Syscall(syscall as u32)
+    /// Pseudo numbers are translated by libseccomp for each architecture
+    /// of the context, and rules of them only match on the architectures
+    /// having the syscall. See `Context::syscall` to check the architectures.
+    ///
+    /// Return an `Err` if libseccomp does not know the name.
+    pub fn from_name(name: &str) -> Result<Syscall> {
+        let no_such_syscall = || Error::Seccomp(format!("No such syscall: {:?}", name));
+        let c_name = CString::new(name).map_err(|_| no_such_syscall())?;
+        let syscall = unsafe { seccomp_syscall_resolve_name(c_name.as_ptr()) };
+        if syscall == __NR_SCMP_ERROR {
+            return Err(no_such_syscall());
+        }
+        Ok(Syscall(syscall as u32))
+    }
+
+    /// Resolve the name of a syscall on the native architecture.
+    ///
+    /// Return `None` if libseccomp knows the syscall
+    /// but it is not available on the native architecture,
+    /// or an `Err` if the name is unknown.
+    fn resolve(name: &str) -> Result<Option<Syscall>> {
+        let syscall = Syscall::from_name(name)?;
+        if (syscall.0 as i32) < 0 {
+            return Ok(None);
+        }
+        Ok(Some(syscall))
     }
 }
 
@@ -32,68 +63,201 @@ impl Deref for Syscall {
     }
 }
 
+/// Architecture token of libseccomp.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Arch(u32);
+
+impl Arch {
+    /// The native architecture of the system.
+    pub fn native() -> Arch {
+        Arch(unsafe { seccomp_arch_native() })
+    }
+
+    /// Resolve the name of an architecture, such as `x86`, `x86_64` or `x32`.
+    ///
+    /// Return an `Err` if libseccomp does not know the architecture.
+    pub fn from_name(name: &str) -> Result<Arch> {
+        let no_such_arch = || Error::Seccomp(format!("No such architecture: {:?}", name));
+        let c_name = CString::new(name).map_err(|_| no_such_arch())?;
+        let arch = unsafe { seccomp_arch_resolve_name(c_name.as_ptr()) };
+        if arch == 0 {
+            return Err(no_such_arch());
+        }
+        Ok(Arch(arch))
+    }
+
+    /// Get the number of a syscall on the architecture.
+    ///
+    /// The number is only informative. Rules always take the `Syscall`
+    /// resolved by its name, which libseccomp translates
+    /// for every architecture of the context.
+    ///
+    /// Return an `Err` if the syscall is not available on the architecture.
+    pub fn syscall_number(self, name: &str) -> Result<i32> {
+        let no_such_syscall =
+            || Error::Seccomp(format!("No such syscall on {:?}: {:?}", self, name));
+        let c_name = CString::new(name).map_err(|_| no_such_syscall())?;
+        let syscall = unsafe { seccomp_syscall_resolve_name_arch(self.0, c_name.as_ptr()) };
+        if syscall < 0 {
+            return Err(no_such_syscall());
+        }
+        Ok(syscall)
+    }
+}
+
 /// Seccomp context.
+///
+/// A new context only contains the native architecture,
+/// and the process is killed by syscalls of any other architecture.
 pub struct Context {
     ctx: scmp_filter_ctx,
+    /// Architectures in the context.
+    archs: Vec<Arch>,
 }
 
 /// Default seccomp context with the `kill` action.
 impl Default for Context {
     fn default() -> Context {
-        Context {
-            ctx: unsafe { seccomp_init(Act::Kill as u32) },
-        }
+        Context::new(Act::Kill)
     }
 }
 
 impl Context {
     /// Create a new seccomp context with the given action.
     pub fn new(act: Act) -> Context {
-        Context {
-            ctx: unsafe { seccomp_init(act as u32) },
+        let mut ctx = Context {
+            ctx: unsafe { seccomp_init(act.to_raw()) },
+            archs: vec![Arch::native()],
+        };
+        // Keep the default of libseccomp, which only kills the thread,
+        // if killing the process is not supported
+        let _ = ctx.set_bad_arch_act(Act::KillProcess);
+        ctx
+    }
+
+    /// Create a seccomp context from a predefined profile,
+    /// such as `static`, `jvm`, `python` or `compiler`.
+    ///
+    /// The process is killed on any syscall outside the profile,
+    /// except the denied syscalls of the profile which fail with `EPERM`.
+    /// Syscalls unavailable on the native architecture are skipped.
+    ///
+    /// Return an `Err` if there is no profile named `name`
+    /// or the profile contains an unknown syscall.
+    pub fn from_profile(name: &str) -> Result<Context> {
+        let profile = profiles::get_profile(name)
+            .ok_or_else(|| Error::Seccomp(format!("No such profile: {:?}", name)))?;
+        let mut ctx = Context::new(Act::KillProcess);
+        for syscall in &profile.syscalls {
+            if let Some(syscall) = Syscall::resolve(syscall)? {
+                ctx.add_rule(Rule::whitelist(syscall))?;
+            }
+        }
+        for syscall in &profile.denied {
+            if let Some(syscall) = Syscall::resolve(syscall)? {
+                ctx.add_rule(Rule::new(Act::Errno(nix::libc::EPERM as u16), syscall))?;
+            }
+        }
+        Ok(ctx)
+    }
+
+    /// Add an architecture to the context.
+    ///
+    /// Rules of the context also apply to syscalls of the architecture.
+    pub fn add_arch(&mut self, arch: Arch) -> Result<()> {
+        let rc = unsafe { seccomp_arch_add(self.ctx, arch.0) };
+        check_rc(rc, "add architecture")?;
+        self.archs.push(arch);
+        Ok(())
+    }
+
+    /// Remove an architecture from the context.
+    pub fn remove_arch(&mut self, arch: Arch) -> Result<()> {
+        let rc = unsafe { seccomp_arch_remove(self.ctx, arch.0) };
+        check_rc(rc, "remove architecture")?;
+        self.archs.retain(|&a| a != arch);
+        Ok(())
+    }
+
+    /// Check whether the architecture is in the context.
+    pub fn has_arch(&self, arch: Arch) -> bool {
+        unsafe { seccomp_arch_exist(self.ctx, arch.0) == 0 }
+    }
+
+    /// Resolve the name of a syscall available on any architecture of the context.
+    ///
+    /// Return an `Err` if no architecture of the context has the syscall.
+    pub fn syscall(&self, name: &str) -> Result<Syscall> {
+        let syscall = Syscall::from_name(name)?;
+        if !self
+            .archs
+            .iter()
+            .any(|arch| arch.syscall_number(name).is_ok())
+        {
+            return Err(Error::Seccomp(format!(
+                "Syscall {:?} is not available on any architecture of the context",
+                name
+            )));
         }
+        Ok(syscall)
+    }
+
+    /// Set the action for syscalls of architectures not in the context.
+    pub fn set_bad_arch_act(&mut self, act: Act) -> Result<()> {
+        let rc = unsafe {
+            seccomp_attr_set(
+                self.ctx,
+                scmp_filter_attr_SCMP_FLTATR_ACT_BADARCH,
+                act.to_raw(),
+            )
+        };
+        check_rc(rc, "set bad architecture action")
     }
 
     /// Add a new rule to the context.
-    pub fn add_rule(&mut self, rule: Rule) -> nix::Result<()> {
+    pub fn add_rule(&mut self, rule: Rule) -> Result<()> {
         let rc = unsafe {
             seccomp_rule_add_array(
                 self.ctx,
-                rule.act as u32,
+                rule.act.to_raw(),
                 *rule.syscall as i32,
                 rule.args().len() as u32,
                 rule.to_arg_cmp().as_ptr(),
             )
         };
 
-        if rc < 0 {
-            return Err(nix::Error::from(nix::errno::from_i32(-rc)));
-        }
-
-        Ok(())
+        check_rc(rc, "add rule")
     }
 
     /// Reset the context with a new default action.
-    pub fn reset(&mut self, default_act: Act) -> nix::Result<()> {
-        let rc = unsafe { seccomp_reset(self.ctx, default_act as u32) };
-
-        if rc < 0 {
-            return Err(nix::Error::from_errno(nix::errno::from_i32(-rc)));
-        }
-
+    ///
+    /// Rules and architectures other than the native one are removed.
+    pub fn reset(&mut self, default_act: Act) -> Result<()> {
+        let rc = unsafe { seccomp_reset(self.ctx, default_act.to_raw()) };
+        check_rc(rc, "reset context")?;
+        self.archs = vec![Arch::native()];
+        let _ = self.set_bad_arch_act(Act::KillProcess);
         Ok(())
     }
 
     /// Load the current seccomp filter into the kernel.
-    pub fn load(&self) -> nix::Result<()> {
+    pub fn load(&self) -> Result<()> {
         let rc = unsafe { seccomp_load(self.ctx) };
+        check_rc(rc, "load context")
+    }
+}
 
-        if rc < 0 {
-            return Err(nix::Error::from_errno(nix::errno::from_i32(-rc)));
-        }
-
-        Ok(())
+/// Convert the return code of a libseccomp function into a result.
+fn check_rc(rc: i32, operation: &str) -> Result<()> {
+    if rc < 0 {
+        let errno = nix::errno::from_i32(-rc);
+        return Err(Error::Seccomp(format!(
+            "Failed to {}: {}",
+            operation,
+            errno.desc()
+        )));
     }
+    Ok(())
 }
 
 /// Release the context.
@@ -112,7 +276,7 @@ unsafe impl Sync for Context {}
 pub struct Rule {
     act: Act,
     syscall: Syscall,
-    pattern: Vec<(CmpOp, i64)>,
+    pattern: Vec<ArgCmp>,
 }
 
 impl Rule {
@@ -135,57 +299,117 @@ impl Rule {
         Rule::new(Act::Kill, syscall)
     }
 
-    /// Add the new argument to the rule filter.
-    pub fn match_arg(&mut self, op: CmpOp, arg: i64) {
-        self.pattern.push((op, arg));
+    /// Only match the syscall when its `index`-th argument (from 0 to 5)
+    /// compares to `datum` with `op`.
+    ///
+    /// All comparisons of a rule must hold for the rule to match.
+    /// Each argument can only be compared once in a rule,
+    /// otherwise libseccomp rejects the rule when it is added to a context.
+    pub fn match_arg(&mut self, index: u32, op: CmpOp, datum: u64) -> &mut Rule {
+        self.pattern.push(ArgCmp { index, op, datum });
+        self
     }
 
-    /// Get the arguments of the rule.
-    pub fn args(&self) -> &Vec<(CmpOp, i64)> {
+    /// Get the argument comparisons of the rule.
+    pub fn args(&self) -> &[ArgCmp] {
         &self.pattern
     }
 
     fn to_arg_cmp(&self) -> Vec<scmp_arg_cmp> {
-        self.pattern
-            .iter()
-            .enumerate()
-            .map(|(index, (op, value))| scmp_arg_cmp {
-                arg: index as u32,
-                op: *op as u32,
-                datum_a: *value as u64,
-                datum_b: 0,
-            })
-            .collect()
+        self.pattern.iter().map(|cmp| cmp.to_raw()).collect()
+    }
+}
+
+/// Comparison of a syscall argument in a rule.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArgCmp {
+    /// Position of the argument in the syscall.
+    pub index: u32,
+    /// Comparing operation.
+    pub op: CmpOp,
+    /// Value the argument is compared to.
+    pub datum: u64,
+}
+
+impl ArgCmp {
+    fn to_raw(self) -> scmp_arg_cmp {
+        let (op, datum_a, datum_b) = match self.op {
+            CmpOp::NE => (scmp_compare_SCMP_CMP_NE, self.datum, 0),
+            CmpOp::LT => (scmp_compare_SCMP_CMP_LT, self.datum, 0),
+            CmpOp::LE => (scmp_compare_SCMP_CMP_LE, self.datum, 0),
+            CmpOp::EQ => (scmp_compare_SCMP_CMP_EQ, self.datum, 0),
+            CmpOp::GE => (scmp_compare_SCMP_CMP_GE, self.datum, 0),
+            CmpOp::GT => (scmp_compare_SCMP_CMP_GT, self.datum, 0),
+            // libseccomp compares `arg & datum_a == datum_b`
+            CmpOp::MaskedEq(mask) => (scmp_compare_SCMP_CMP_MASKED_EQ, mask, self.datum),
+        };
+        scmp_arg_cmp {
+            arg: self.index,
+            op,
+            datum_a,
+            datum_b,
+        }
     }
 }
 
 /// Support actions in seccomp.
-#[allow(dead_code)]
-#[derive(Copy, Clone)]
-#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Act {
-    Allow = SCMP_ACT_ALLOW,
-    Kill = SCMP_ACT_KILL,
-    // KillProcess = SCMP_ACT_KILL_PROCESS,
-    // Trap = SCMP_ACT_TRAP,
-    // Errno = SCMP_ACT_ERRNO,
-    // Trace = SCMP_ACT_TRACE,
-    // Log = SCMP_ACT_LOG,
+    /// Allow the syscall.
+    Allow,
+    /// Kill the thread calling the syscall.
+    Kill,
+    /// Kill the whole process calling the syscall.
+    KillProcess,
+    /// Send a `SIGSYS` signal to the thread calling the syscall.
+    Trap,
+    /// Make the syscall fail with the errno instead of running it.
+    Errno(u16),
+    /// Notify the tracer with the value if there is a tracer,
+    /// otherwise the syscall fails with `ENOSYS`.
+    Trace(u16),
+    /// Allow the syscall after logging it.
+    Log,
+}
+
+impl Act {
+    /// The action value used by libseccomp.
+    fn to_raw(self) -> u32 {
+        // `SCMP_ACT_ERRNO` and `SCMP_ACT_TRACE` are macros in the header
+        const SCMP_ACT_ERRNO: u32 = 0x0005_0000;
+        const SCMP_ACT_TRACE: u32 = 0x7ff0_0000;
+        match self {
+            Act::Allow => SCMP_ACT_ALLOW,
+            Act::Kill => SCMP_ACT_KILL,
+            Act::KillProcess => SCMP_ACT_KILL_PROCESS,
+            Act::Trap => SCMP_ACT_TRAP,
+            Act::Errno(errno) => SCMP_ACT_ERRNO | u32::from(errno),
+            Act::Trace(value) => SCMP_ACT_TRACE | u32::from(value),
+            Act::Log => SCMP_ACT_LOG,
+        }
+    }
 }
 
 /// Comparing operations the filter uses.
-#[derive(Copy, Clone)]
-#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CmpOp {
-    // Min = scmp_compare__SCMP_CMP_MIN,
-    NE = scmp_compare_SCMP_CMP_NE,
-    LT = scmp_compare_SCMP_CMP_LT,
-    LE = scmp_compare_SCMP_CMP_LE,
-    EQ = scmp_compare_SCMP_CMP_EQ,
-    GE = scmp_compare_SCMP_CMP_GE,
-    GT = scmp_compare_SCMP_CMP_GT,
-    // MaskedEq = scmp_compare_SCMP_CMP_MASKED_EQ,
-    // Max = scmp_compare__SCMP_CMP_MAX,
+    /// Not equal.
+    NE,
+    /// Less than.
+    LT,
+    /// Less than or equal.
+    LE,
+    /// Equal.
+    EQ,
+    /// Greater than or equal.
+    GE,
+    /// Greater than.
+    GT,
+    /// Equal after masking the argument with the mask.
+    #[serde(rename = "masked_eq")]
+    MaskedEq(u64),
 }
 
 /// Extra features make Command run in a new container.
@@ -198,10 +422,17 @@ impl CommandExt for Command {
     fn seccomp(&mut self, ctx: Context) -> &mut Command {
         unsafe {
             self.pre_exec(move || {
-                ctx.load().expect("Failed to load seccomp context");
+                // Avoid allocation of the error message in the child process
+                let rc = seccomp_load(ctx.ctx);
+                if rc < 0 {
+                    return Err(io::Error::from_raw_os_error(-rc));
+                }
                 Ok(())
             });
         }
         self
     }
 }
+
+#[cfg(test)]
+mod tests;
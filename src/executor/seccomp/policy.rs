@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+use super::{Act, Arch, ArgCmp, Context, Rule};
+use crate::error::{Error, Result};
+
+/// Declarative seccomp policy which can be kept in a JSON file.
+///
+/// ```json
+/// {
+///     "default": "kill_process",
+///     "architectures": ["x86"],
+///     "rules": [
+///         { "syscall": "read" },
+///         { "syscall": "ptrace", "action": { "errno": 1 } },
+///         {
+///             "syscall": "open",
+///             "args": [{ "index": 1, "op": { "masked_eq": 3 }, "datum": 0 }]
+///         }
+///     ]
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    /// Action for syscalls matching no rule, `kill_process` by default.
+    #[serde(default = "default_act")]
+    pub default: Act,
+    /// Architectures allowed besides the native one.
+    #[serde(default)]
+    pub architectures: Vec<String>,
+    /// Rules of the policy.
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+}
+
+/// Rule of a seccomp policy.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyRule {
+    /// Name of the syscall.
+    pub syscall: String,
+    /// Action for the syscall, `allow` by default.
+    #[serde(default = "allow_act")]
+    pub action: Act,
+    /// Argument comparisons which must all hold for the rule to match.
+    #[serde(default)]
+    pub args: Vec<ArgCmp>,
+}
+
+fn default_act() -> Act {
+    Act::KillProcess
+}
+
+fn allow_act() -> Act {
+    Act::Allow
+}
+
+impl Policy {
+    /// Parse a policy from JSON.
+    ///
+    /// Return an `Err` if the policy is malformed or invalid.
+    pub fn from_json(json: &str) -> Result<Policy> {
+        let policy: Policy = serde_json::from_str(json)
+            .map_err(|e| Error::Config(format!("Malformed seccomp policy: {}", e)))?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Read a policy from a JSON file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Policy> {
+        Policy::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Check every architecture of the policy is known, every syscall is
+    /// available on the native architecture or one of the architectures,
+    /// and every argument index is less than 6 and compared once in a rule.
+    pub fn validate(&self) -> Result<()> {
+        let mut archs = vec![Arch::native()];
+        for arch in &self.architectures {
+            archs.push(Arch::from_name(arch).map_err(|_| {
+                Error::Config(format!("Unknown architecture in seccomp policy: {}", arch))
+            })?);
+        }
+        let unknown: Vec<&str> = self
+            .rules
+            .iter()
+            .map(|rule| rule.syscall.as_str())
+            .filter(|syscall| {
+                !archs
+                    .iter()
+                    .any(|arch| arch.syscall_number(syscall).is_ok())
+            })
+            .collect();
+        if !unknown.is_empty() {
+            return Err(Error::Config(format!(
+                "Unknown syscalls in seccomp policy: {}",
+                unknown.join(", ")
+            )));
+        }
+        if let Some(rule) = self
+            .rules
+            .iter()
+            .find(|rule| rule.args.iter().any(|arg| arg.index >= 6))
+        {
+            return Err(Error::Config(format!(
+                "Argument index out of range in seccomp rule for {}",
+                rule.syscall
+            )));
+        }
+        if let Some(rule) = self.rules.iter().find(|rule| {
+            rule.args
+                .iter()
+                .enumerate()
+                .any(|(i, arg)| rule.args[..i].iter().any(|a| a.index == arg.index))
+        }) {
+            return Err(Error::Config(format!(
+                "Argument compared more than once in seccomp rule for {}",
+                rule.syscall
+            )));
+        }
+        Ok(())
+    }
+
+    /// Build a seccomp context with the policy.
+    pub fn build(&self) -> Result<Context> {
+        self.validate()?;
+        let mut ctx = Context::new(self.default);
+        for arch in &self.architectures {
+            let arch = Arch::from_name(arch)?;
+            if !ctx.has_arch(arch) {
+                ctx.add_arch(arch)?;
+            }
+        }
+        for policy_rule in &self.rules {
+            let mut rule = Rule::new(policy_rule.action, ctx.syscall(&policy_rule.syscall)?);
+            for arg in &policy_rule.args {
+                rule.match_arg(arg.index, arg.op, arg.datum);
+            }
+            ctx.add_rule(rule)?;
+        }
+        Ok(ctx)
+    }
+}
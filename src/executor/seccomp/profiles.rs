@@ -0,0 +1,30 @@
+use std::collections::HashMap;
+
+use bincode;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+const PROFILES_BINCODE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/seccomp_profiles"));
+
+lazy_static! {
+    static ref PROFILES: HashMap<String, Profile> = bincode::deserialize(PROFILES_BINCODE).unwrap();
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Profile {
+    pub description: String,
+    pub syscalls: Vec<String>,
+    /// Syscalls failing with `EPERM` instead of killing the process,
+    /// such as `socket` whose failure makes glibc look up names in local files.
+    #[serde(default)]
+    pub denied: Vec<String>,
+}
+
+pub fn get_profile(name: &str) -> Option<&'static Profile> {
+    PROFILES.get(name)
+}
+
+/// Names of the predefined seccomp profiles.
+pub fn profile_names() -> impl Iterator<Item = &'static str> {
+    PROFILES.keys().map(String::as_str)
+}
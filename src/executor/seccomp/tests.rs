@@ -0,0 +1,335 @@
+use super::*;
+
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Output;
+
+#[cfg(feature = "gcc")]
+use crate::compiler::Compiler;
+#[cfg(feature = "gcc")]
+use crate::structures::Source;
+
+const PROFILES: &[&str] = &["static", "jvm", "python", "compiler"];
+
+#[cfg(feature = "gcc")]
+const HELLO_WORLD: &str = r#"
+#include<stdio.h>
+int main() {
+    printf("hello, world\n");
+    return 0;
+}
+"#;
+
+#[cfg(feature = "gcc")]
+const SOCKET: &str = r#"
+#define _POSIX_C_SOURCE 200809L
+#include<sys/socket.h>
+int main() {
+    socket(AF_INET, SOCK_STREAM, 0);
+    return 0;
+}
+"#;
+
+#[cfg(feature = "gcc")]
+fn compile(code: &str) -> Result<tempfile::TempPath> {
+    let source = Source {
+        language: String::from("c.gcc"),
+        code: String::from(code),
+    };
+    let executable_file = tempfile::NamedTempFile::new()?.into_temp_path();
+    let output = Compiler::new(&source.language)
+        .unwrap()
+        .compile(&source, &executable_file)?;
+    assert!(output.status.success());
+    Ok(executable_file)
+}
+
+#[test]
+fn test_profiles() -> Result<()> {
+    for name in PROFILES {
+        assert!(profile_names().any(|profile| profile == *name));
+        Context::from_profile(name)?;
+    }
+    assert!(Context::from_profile("no_such_profile").is_err());
+
+    // Unknown names are errors, only syscalls of other architectures are skipped
+    assert!(Syscall::resolve("no_such_syscall").is_err());
+    #[cfg(target_arch = "x86_64")]
+    assert!(Syscall::resolve("socketcall")?.is_none());
+    Ok(())
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_profile_syscalls() {
+    for name in profile_names() {
+        let profile = profiles::get_profile(name).unwrap();
+        for syscall in profile.syscalls.iter().chain(&profile.denied) {
+            let native = Arch::native().syscall_number(syscall);
+            assert!(native.is_ok(), "{}: {}", name, syscall);
+        }
+    }
+}
+
+#[cfg(feature = "gcc")]
+#[test]
+fn test_static_profile() -> Result<()> {
+    let executable_file = compile(HELLO_WORLD)?;
+    let output = Command::new(&executable_file)
+        .seccomp(Context::from_profile("static")?)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"hello, world\n");
+
+    let executable_file = compile(SOCKET)?;
+    let status = Command::new(&executable_file)
+        .seccomp(Context::from_profile("static")?)
+        .status()?;
+    assert_eq!(status.signal(), Some(nix::libc::SIGSYS));
+    Ok(())
+}
+
+/// Run the program under the profile.
+///
+/// Return `None` if the program is not installed.
+fn run_with_profile(profile: &str, program: &str, args: &[&str]) -> Result<Option<Output>> {
+    match Command::new(program).args(args).output() {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!("Skip the test: {} is not installed", program);
+            return Ok(None);
+        }
+        result => assert!(result?.status.success()),
+    }
+    let output = Command::new(program)
+        .args(args)
+        .seccomp(Context::from_profile(profile)?)
+        .output()?;
+    Ok(Some(output))
+}
+
+#[test]
+fn test_python_profile() -> Result<()> {
+    if let Some(output) = run_with_profile("python", "python3", &["-c", "print(1 + 2)"])? {
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"3\n");
+    }
+    Ok(())
+}
+
+const JAVA_MAIN: &str = r#"
+import java.io.IOException;
+import java.net.Socket;
+import java.util.ArrayList;
+import java.util.List;
+
+public class Main {
+    public static void main(String[] args) throws InterruptedException {
+        List<Integer> numbers = new ArrayList<>();
+        for (int i = 1; i <= 100; i++) {
+            numbers.add(i);
+        }
+        Thread thread = new Thread(() -> System.out.println(numbers.stream().mapToInt(i -> i).sum()));
+        thread.start();
+        thread.join();
+        try (Socket socket = new Socket("127.0.0.1", 80)) {
+            System.out.println("connected");
+        } catch (IOException e) {
+            System.out.println("no network");
+        }
+    }
+}
+"#;
+
+#[test]
+fn test_jvm_profile() -> Result<()> {
+    if let Some(output) = run_with_profile("jvm", "java", &["-version"])? {
+        assert!(output.status.success());
+    }
+
+    // The class is compiled outside of the profile
+    let dir = tempfile::tempdir()?;
+    let source_file = dir.path().join("Main.java");
+    std::fs::write(&source_file, JAVA_MAIN)?;
+    match Command::new("javac").arg(&source_file).status() {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!("Skip the test: javac is not installed");
+            return Ok(());
+        }
+        status => assert!(status?.success()),
+    }
+    let class_path = dir.path().to_str().unwrap();
+    let output = run_with_profile("jvm", "java", &["-cp", class_path, "Main"])?.unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"5050\nno network\n");
+    Ok(())
+}
+
+#[cfg(feature = "gcc")]
+#[test]
+fn test_compiler_profile() -> Result<()> {
+    let mut source_file = tempfile::Builder::new().suffix(".c").tempfile()?;
+    io::Write::write_all(&mut source_file, HELLO_WORLD.as_bytes())?;
+    let source_file = source_file.into_temp_path();
+    let executable_file = tempfile::NamedTempFile::new()?.into_temp_path();
+    let args = [
+        source_file.to_str().unwrap(),
+        "-o",
+        executable_file.to_str().unwrap(),
+        "--static",
+    ];
+    let output = run_with_profile("compiler", "gcc", &args)?.unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        Command::new(&executable_file).output()?.stdout,
+        b"hello, world\n"
+    );
+    Ok(())
+}
+
+const POLICY: &str = r#"
+{
+    "default": "allow",
+    "rules": [
+        {
+            "syscall": "openat",
+            "action": { "errno": 13 },
+            "args": [{ "index": 2, "op": { "masked_eq": 3 }, "datum": 1 }]
+        },
+        { "syscall": "ptrace", "action": "kill_process" }
+    ]
+}
+"#;
+
+#[cfg(feature = "gcc")]
+const OPEN_FILES: &str = r#"
+#include<stdio.h>
+int main() {
+    if (fopen("/dev/null", "r") == NULL) { return 1; }
+    if (fopen("/dev/null", "w") != NULL) { return 2; }
+    return 0;
+}
+"#;
+
+#[test]
+fn test_policy() -> Result<()> {
+    let policy = Policy::from_json(POLICY)?;
+    assert_eq!(policy.default, Act::Allow);
+    assert!(policy.architectures.is_empty());
+    assert_eq!(
+        policy.rules,
+        vec![
+            PolicyRule {
+                syscall: String::from("openat"),
+                action: Act::Errno(13),
+                args: vec![ArgCmp {
+                    index: 2,
+                    op: CmpOp::MaskedEq(3),
+                    datum: 1,
+                }],
+            },
+            PolicyRule {
+                syscall: String::from("ptrace"),
+                action: Act::KillProcess,
+                args: Vec::new(),
+            },
+        ]
+    );
+    policy.build()?;
+
+    let policy = Policy::from_json(r#"{ "rules": [{ "syscall": "read" }] }"#)?;
+    assert_eq!(policy.default, Act::KillProcess);
+    assert_eq!(policy.rules[0].action, Act::Allow);
+    Ok(())
+}
+
+#[test]
+fn test_invalid_policy() {
+    assert!(Policy::from_json(r#"{ "rules": [{ "action": "allow" }] }"#).is_err());
+    assert!(Policy::from_json(r#"{ "default": "ignore" }"#).is_err());
+    assert!(Policy::from_json(r#"{ "architectures": ["no_such_arch"] }"#).is_err());
+
+    let unknown = r#"{ "rules": [{ "syscall": "read" }, { "syscall": "no_such_syscall" }] }"#;
+    match Policy::from_json(unknown) {
+        Err(Error::Config(message)) => assert!(message.contains("no_such_syscall")),
+        _ => panic!("unknown syscalls should be rejected"),
+    }
+
+    let out_of_range = r#"{
+        "rules": [{ "syscall": "read", "args": [{ "index": 6, "op": "eq", "datum": 0 }] }]
+    }"#;
+    assert!(Policy::from_json(out_of_range).is_err());
+
+    // libseccomp can not compare an argument twice in a rule
+    let duplicate = r#"{
+        "rules": [{
+            "syscall": "read",
+            "args": [
+                { "index": 2, "op": "ge", "datum": 1 },
+                { "index": 2, "op": "le", "datum": 4096 }
+            ]
+        }]
+    }"#;
+    match Policy::from_json(duplicate) {
+        Err(Error::Config(message)) => assert!(message.contains("more than once")),
+        _ => panic!("duplicate argument comparisons should be rejected"),
+    }
+}
+
+#[cfg(feature = "gcc")]
+#[test]
+fn test_policy_args() -> Result<()> {
+    let executable_file = compile(OPEN_FILES)?;
+    let status = Command::new(&executable_file)
+        .seccomp(Policy::from_json(POLICY)?.build()?)
+        .status()?;
+    assert!(status.success());
+    Ok(())
+}
+
+#[test]
+fn test_arch() -> Result<()> {
+    let native = Arch::native();
+    assert!(Arch::from_name("no_such_arch").is_err());
+
+    let mut ctx = Context::default();
+    assert!(ctx.has_arch(native));
+    assert!(ctx.add_arch(native).is_err());
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        assert_eq!(native, Arch::from_name("x86_64")?);
+        let x86 = Arch::from_name("x86")?;
+        assert!(!ctx.has_arch(x86));
+        ctx.add_rule(Rule::whitelist(Syscall::from_name("write")?))?;
+        ctx.add_arch(x86)?;
+        assert!(ctx.has_arch(x86));
+        ctx.add_rule(Rule::whitelist(Syscall::from_name("read")?))?;
+        ctx.remove_arch(x86)?;
+        assert!(!ctx.has_arch(x86));
+
+        assert_eq!(native.syscall_number("write")?, 1);
+        assert_eq!(x86.syscall_number("write")?, 4);
+        assert!(x86.syscall_number("no_such_syscall").is_err());
+
+        let policy = Policy::from_json(r#"{ "architectures": ["x86", "x86_64"] }"#)?;
+        assert!(policy.build()?.has_arch(x86));
+
+        // Syscalls only available on x86 are resolved to pseudo numbers
+        assert!(Syscall::from_name("mmap2").is_ok());
+        assert!(ctx.syscall("mmap2").is_err());
+        ctx.add_arch(x86)?;
+        ctx.add_rule(Rule::whitelist(ctx.syscall("mmap2")?))?;
+        ctx.remove_arch(x86)?;
+        assert!(ctx.syscall("mmap2").is_err());
+
+        let x86_only = r#"{ "rules": [{ "syscall": "socketcall" }] }"#;
+        assert!(Policy::from_json(x86_only).is_err());
+        let x86_only = r#"{ "architectures": ["x86"], "rules": [{ "syscall": "socketcall" }] }"#;
+        assert!(Policy::from_json(x86_only)?.build()?.has_arch(x86));
+    }
+
+    ctx.set_bad_arch_act(Act::Errno(1))?;
+    ctx.reset(Act::Allow)?;
+    assert!(ctx.has_arch(native));
+    Ok(())
+}
@@ -73,10 +73,10 @@ fn test_cgroup() -> io::Result<()> {
     let cg_ctx = cg_ctx!();
 
     let start_time = Instant::now();
-    let exit_status = Command::new(&PROGRAM)
+    let exit_status = Command::new(PROGRAM)
         .stdin(File::open(&input_file)?)
         .stdout(File::create(&output_file)?)
-        .cgroup(cg_ctx.clone())
+        .cgroup(&cg_ctx)
         .spawn()?
         .wait()?;
     let cpu_usage = cg_ctx.cpuacct_controller().unwrap().usage()?;
@@ -120,7 +120,7 @@ fn test_unshare() -> io::Result<()> {
     let input_file = input_file!();
     let output_file = output_file!();
 
-    let exit_status = Command::new(&PROGRAM)
+    let exit_status = Command::new(PROGRAM)
         .stdin(File::open(&input_file)?)
         .stdout(File::create(&output_file)?)
         .unshare_all_ns()
@@ -138,7 +138,7 @@ fn test_timeout() -> io::Result<()> {
     let input_file = timeout_input_file!();
     let output_file = output_file!();
 
-    let exit_status = Command::new(&PROGRAM)
+    let exit_status = Command::new(PROGRAM)
         .stdin(File::open(&input_file)?)
         .stdout(File::create(&output_file)?)
         .spawn()?
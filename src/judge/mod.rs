@@ -0,0 +1,684 @@
+//! Judge a task from compiling the source to checking every test case.
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, prelude::*};
+use std::mem;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{mpsc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nix::libc;
+use serde::{Deserialize, Serialize};
+use tempfile::{self, TempPath};
+
+use crate::checker::{CheckResult, Checker, Verdict};
+use crate::compiler::Compiler;
+use crate::error::{Error, Result};
+use crate::executor::cgroup::{self, CommandExt as _};
+use crate::structures::*;
+
+/// Result of judging a whole task.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Judgement {
+    /// The overall verdict of the task.
+    ///
+    /// It is the first report which is not accepted,
+    /// or an accepted report with the maximum resource usage of all cases.
+    pub verdict: Report,
+    /// Reports of all test cases in order.
+    pub reports: Vec<Report>,
+    /// Score of the task if the problem has subtasks.
+    pub score: Option<Score>,
+}
+
+/// Points got by the source.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    /// Sum of the points of all subtasks.
+    pub points: f64,
+    /// Points of each subtask in order.
+    pub subtasks: Vec<f64>,
+}
+
+impl Judgement {
+    /// Generate a judgement with the reports of all test cases.
+    pub fn from_reports(reports: Vec<Report>) -> Judgement {
+        let mut max_usage = Resource::new(Duration::from_secs(0), Duration::from_secs(0), 0);
+        for report in &reports {
+            match report {
+                Report::Accepted { resource_usage } => {
+                    max_usage.cpu_time = max_usage.cpu_time.max(resource_usage.cpu_time);
+                    max_usage.real_time = max_usage.real_time.max(resource_usage.real_time);
+                    max_usage.memory = max_usage.memory.max(resource_usage.memory);
+                }
+                _ => {
+                    return Judgement {
+                        verdict: report.clone(),
+                        reports,
+                        score: None,
+                    };
+                }
+            }
+        }
+        Judgement {
+            verdict: Report::Accepted {
+                resource_usage: max_usage,
+            },
+            reports,
+            score: None,
+        }
+    }
+
+    /// Score the judgement by `subtasks`.
+    ///
+    /// Test cases without a report get no point, so does a subtask without any case.
+    /// A subtask gets no point if any of its dependencies does not get full points.
+    pub fn with_subtasks(mut self, subtasks: &[Subtask]) -> Judgement {
+        if subtasks.is_empty() {
+            return self;
+        }
+
+        let case_score = |i: &usize| self.reports.get(*i).map_or(0.0, Report::score);
+        let mut scores: Vec<f64> = Vec::with_capacity(subtasks.len());
+        for subtask in subtasks {
+            let dependencies_passed = subtask
+                .dependencies
+                .iter()
+                .all(|&i| i < scores.len() && scores[i] >= subtasks[i].points);
+            let score = if !dependencies_passed || subtask.cases.is_empty() {
+                0.0
+            } else {
+                match subtask.policy {
+                    Policy::Min => {
+                        let min = subtask.cases.iter().map(case_score).fold(1.0, f64::min);
+                        subtask.points * min
+                    }
+                    Policy::Sum => {
+                        let sum: f64 = subtask.cases.iter().map(case_score).sum();
+                        subtask.points * sum / subtask.cases.len() as f64
+                    }
+                    Policy::AllOrNothing => {
+                        if subtask.cases.iter().all(|i| case_score(i) >= 1.0) {
+                            subtask.points
+                        } else {
+                            0.0
+                        }
+                    }
+                }
+            };
+            scores.push(score);
+        }
+
+        self.score = Some(Score {
+            points: scores.iter().sum(),
+            subtasks: scores,
+        });
+        self
+    }
+
+    /// Generate a judgement whose verdict is `report` and contains no test case report.
+    fn failed(report: Report) -> Judgement {
+        Judgement {
+            verdict: report,
+            reports: Vec::new(),
+            score: None,
+        }
+    }
+}
+
+/// A judge which compiles the source, runs it on every test case
+/// with the resource limit of the problem and checks the output.
+pub struct Judge<'a> {
+    checker: Checker<'a>,
+    /// Resource limit of special judge programs.
+    spj_limit: Resource,
+    /// Resource limit of interactor programs.
+    interactor_limit: Resource,
+    /// Compiled special judge and interactor programs.
+    ///
+    /// Each source is only compiled once
+    /// and the executable file is kept as long as the judge.
+    program_cache: Mutex<HashMap<Source, Option<TempPath>>>,
+    /// Cgroup context in which all programs are run.
+    cgroup_parent: Option<&'a cgroup::Context>,
+}
+
+/// The way to check the output of the source.
+enum Mode {
+    /// Check the output by the checker of the judge.
+    Normal,
+    /// Check the output by the special judge program.
+    Special(PathBuf),
+    /// Interact with the interactor program.
+    Interactive(PathBuf),
+}
+
+impl<'a> Judge<'a> {
+    pub fn new() -> Judge<'a> {
+        Default::default()
+    }
+
+    /// Set the checker used to compare the output with the answer.
+    pub fn checker(mut self, checker: Checker<'a>) -> Judge<'a> {
+        self.checker = checker;
+        self
+    }
+
+    /// Set the resource limit of special judge programs.
+    pub fn spj_limit(mut self, limit: Resource) -> Judge<'a> {
+        self.spj_limit = limit;
+        self
+    }
+
+    /// Set the resource limit of interactor programs.
+    pub fn interactor_limit(mut self, limit: Resource) -> Judge<'a> {
+        self.interactor_limit = limit;
+        self
+    }
+
+    /// Run all programs in cgroup contexts nested in `parent`,
+    /// which can limit the resource usage of the judge in total.
+    pub fn cgroup_parent(mut self, parent: &'a cgroup::Context) -> Judge<'a> {
+        self.cgroup_parent = Some(parent);
+        self
+    }
+
+    /// Judge the task.
+    ///
+    /// Return `Err` only if there is a system error such as an io error,
+    /// the failure of the source is reported by the judgement.
+    pub fn judge(&self, task: &Task) -> Result<Judgement> {
+        let subtasks = task.problem.subtasks();
+        check_subtasks(subtasks, task.problem.len())?;
+
+        let executable_file = tempfile::NamedTempFile::new()?.into_temp_path();
+        if !compile(&task.source, &executable_file)? {
+            return Ok(Judgement::failed(Report::CompileError).with_subtasks(subtasks));
+        }
+
+        let (limit, cases, mode) = match &task.problem {
+            Problem::Normal { limit, cases, .. } => (limit, cases, Mode::Normal),
+            Problem::Special {
+                limit, cases, spj, ..
+            } => match self.compile_cached(spj)? {
+                Some(spj) => (limit, cases, Mode::Special(spj)),
+                None => return Ok(Judgement::failed(Report::SystemError).with_subtasks(subtasks)),
+            },
+            Problem::Interactive {
+                limit,
+                cases,
+                interactor,
+                ..
+            } => match self.compile_cached(interactor)? {
+                Some(interactor) => (limit, cases, Mode::Interactive(interactor)),
+                None => return Ok(Judgement::failed(Report::SystemError).with_subtasks(subtasks)),
+            },
+        };
+
+        let reports = cases
+            .iter()
+            .map(|case| self.judge_case(&executable_file, limit, case, &mode))
+            .collect::<Result<_>>()?;
+        Ok(Judgement::from_reports(reports).with_subtasks(subtasks))
+    }
+
+    /// Spawn the command in a new cgroup context limited by `limit`.
+    fn spawn(&self, command: &mut Command, limit: &Resource) -> Result<Running> {
+        let mut builder = cgroup::Builder::new();
+        if let Some(parent) = self.cgroup_parent {
+            builder = builder.parent(parent);
+        }
+        let cg_ctx = limited_cgroup(builder, limit)?;
+        let start_time = Instant::now();
+        let child = command.cgroup(&cg_ctx).spawn()?;
+        Ok(Running {
+            child,
+            cg_ctx,
+            limit: *limit,
+            start_time,
+        })
+    }
+
+    /// Run the executable file on a single test case and check its output.
+    fn judge_case(
+        &self,
+        executable_file: &Path,
+        limit: &Resource,
+        case: &TestCase,
+        mode: &Mode,
+    ) -> Result<Report> {
+        if let Mode::Interactive(interactor) = mode {
+            return self.judge_interactive_case(executable_file, limit, case, interactor);
+        }
+
+        let input_file = ContentFile::new(&case.input, ".in")?;
+        let output_file = temp_file(".out", b"")?;
+
+        let execution = self
+            .spawn(
+                Command::new(executable_file)
+                    .stdin(File::open(&input_file)?)
+                    .stdout(File::create(&output_file)?)
+                    .stderr(Stdio::null()),
+                limit,
+            )?
+            .wait()?;
+        if let Some(report) = execution.failure() {
+            return Ok(report);
+        }
+
+        let result = match mode {
+            Mode::Special(spj) => {
+                let answer_file = ContentFile::new(&case.answer, ".ans")?;
+                let message_file = temp_file(".msg", b"")?;
+                let spj_execution = self
+                    .spawn(
+                        Command::new(spj)
+                            .arg(input_file.as_ref())
+                            .arg(&output_file)
+                            .arg(answer_file.as_ref())
+                            .stdin(Stdio::null())
+                            .stdout(Stdio::null())
+                            .stderr(File::create(&message_file)?),
+                        &self.spj_limit,
+                    )?
+                    .wait()?;
+                match spj_execution.check_result(&message_file)? {
+                    Some(result) => result,
+                    None => return Ok(Report::SystemError),
+                }
+            }
+            _ => {
+                let answer_file = ContentFile::new(&case.answer, ".ans")?;
+                self.checker
+                    .check_files(&output_file, answer_file.as_ref())?
+            }
+        };
+
+        Ok(report(&result, execution.resource_usage))
+    }
+
+    /// Run the executable file with the interactor on a single test case.
+    ///
+    /// The stdout of each program is connected to the stdin of the other one.
+    /// The interactor gets the filesystem path of input file, output file and
+    /// answer file as three arguments, and it should exit successfully only if
+    /// the interaction is correct.
+    /// The content written to the output file by the interactor is then
+    /// compared with the answer by the checker of the judge.
+    fn judge_interactive_case(
+        &self,
+        executable_file: &Path,
+        limit: &Resource,
+        case: &TestCase,
+        interactor: &Path,
+    ) -> Result<Report> {
+        let input_file = ContentFile::new(&case.input, ".in")?;
+        let output_file = temp_file(".out", b"")?;
+        let answer_file = ContentFile::new(&case.answer, ".ans")?;
+
+        let mut running = self.spawn(
+            Command::new(executable_file)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null()),
+            limit,
+        )?;
+        let message_file = temp_file(".msg", b"")?;
+        let interactor_running = self.spawn(
+            Command::new(interactor)
+                .arg(input_file.as_ref())
+                .arg(&output_file)
+                .arg(answer_file.as_ref())
+                .stdin(Stdio::from(running.child.stdout.take().ok_or_else(
+                    || Error::Io(io::Error::new(io::ErrorKind::BrokenPipe, "No stdout")),
+                )?))
+                .stdout(Stdio::from(running.child.stdin.take().ok_or_else(
+                    || Error::Io(io::Error::new(io::ErrorKind::BrokenPipe, "No stdin")),
+                )?))
+                .stderr(File::create(&message_file)?),
+            &self.interactor_limit,
+        );
+        let interactor_running = match interactor_running {
+            Ok(interactor_running) => interactor_running,
+            Err(e) => {
+                let _ = running.child.kill();
+                running.wait()?;
+                return Err(e);
+            }
+        };
+
+        let interactor_handle = thread::spawn(move || interactor_running.wait());
+        let execution = running.wait()?;
+        let interactor_execution = interactor_handle.join().unwrap_or_else(|_| {
+            Err(Error::Io(io::Error::other(
+                "The interactor thread panicked",
+            )))
+        })?;
+
+        // The source may exit abnormally because the interactor has exited,
+        // so the verdict of the interactor takes precedence over runtime error.
+        match execution.failure() {
+            None | Some(Report::RuntimeError) => {}
+            Some(report) => return Ok(report),
+        }
+        match interactor_execution.check_result(&message_file)? {
+            Some(ref result) if result.is_accepted() => {}
+            Some(result) => return Ok(report(&result, execution.resource_usage)),
+            None => return Ok(Report::SystemError),
+        }
+        if let Some(report) = execution.failure() {
+            return Ok(report);
+        }
+
+        let result = self
+            .checker
+            .check_files(&output_file, answer_file.as_ref())?;
+        Ok(report(&result, execution.resource_usage))
+    }
+
+    /// Get the executable file of a special judge or interactor program.
+    ///
+    /// Compile it if it has not been compiled by this judge.
+    /// Return `None` if the program can not be compiled.
+    fn compile_cached(&self, source: &Source) -> Result<Option<PathBuf>> {
+        // The cache is never left in an inconsistent state,
+        // so it is fine to use it even if another thread panicked.
+        let mut program_cache = self
+            .program_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !program_cache.contains_key(source) {
+            let executable_file = tempfile::NamedTempFile::new()?.into_temp_path();
+            let executable_file = if compile(source, &executable_file)? {
+                Some(executable_file)
+            } else {
+                None
+            };
+            program_cache.insert(source.clone(), executable_file);
+        }
+        Ok(program_cache[source]
+            .as_ref()
+            .map(|path| path.to_path_buf()))
+    }
+}
+
+/// Default judge uses the default checker and limits special judge
+/// and interactor programs to 5 seconds of cpu time,
+/// 10 seconds of real time and 256 MiB of memory.
+impl<'a> Default for Judge<'a> {
+    fn default() -> Judge<'a> {
+        let limit = Resource::new(
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+            256 * 1024 * 1024,
+        );
+        Judge {
+            checker: Checker::default(),
+            spj_limit: limit,
+            interactor_limit: limit,
+            program_cache: Mutex::new(HashMap::new()),
+            cgroup_parent: None,
+        }
+    }
+}
+
+/// Interval of checking the time usage of a running program.
+const CPU_TIME_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A program running in a limited cgroup.
+struct Running {
+    child: Child,
+    cg_ctx: cgroup::Context,
+    limit: Resource,
+    start_time: Instant,
+}
+
+impl Running {
+    /// Wait for the program to exit or be killed when it times out.
+    ///
+    /// All processes of the program are killed once its cpu time usage
+    /// exceeds the cpu time limit or it has run for the real time limit.
+    fn wait(mut self) -> Result<Execution> {
+        let cpuacct_controller = self
+            .cg_ctx
+            .cpuacct_controller()
+            .ok_or_else(|| controller_disabled("cpuacct"))?;
+        let (cpu_time, real_time) = (self.limit.cpu_time, self.limit.real_time);
+        let (start_time, cg_ctx) = (self.start_time, &self.cg_ctx);
+        let child = &mut self.child;
+        let cpuacct = &cpuacct_controller;
+        let (tx, rx) = mpsc::channel::<()>();
+        let (exit_status, max_rss) = thread::scope(|scope| {
+            scope.spawn(move || {
+                while let Err(mpsc::RecvTimeoutError::Timeout) = rx.recv_timeout(CPU_TIME_INTERVAL)
+                {
+                    if start_time.elapsed() >= real_time
+                        || cpuacct.usage().is_ok_and(|usage| usage > cpu_time)
+                    {
+                        // Only processes in the context are killed,
+                        // so a reaped pid which has been reused is never signalled
+                        let _ = cg_ctx.kill_all();
+                        break;
+                    }
+                }
+            });
+            let result = wait_with_max_rss(child);
+            let _ = tx.send(());
+            result
+        })?;
+        let memory_controller = self
+            .cg_ctx
+            .memory_controller()
+            .ok_or_else(|| controller_disabled("memory"))?;
+        let resource_usage = Resource::new(
+            cpuacct_controller.usage()?,
+            self.start_time.elapsed(),
+            max_rss,
+        );
+        Ok(Execution {
+            exit_status,
+            resource_usage,
+            oom_killed: memory_controller.oom_killed()?,
+            limit: self.limit,
+        })
+    }
+}
+
+/// Wait for the child process and get its peak resident memory in bytes.
+///
+/// Page cache, such as the cache of a large input file, is not counted
+/// unlike the usage of the memory controller.
+/// The child is reaped by `wait4`, so it can not be waited by `Child` again.
+fn wait_with_max_rss(child: &Child) -> io::Result<(ExitStatus, usize)> {
+    let pid = child.id() as libc::pid_t;
+    let mut status = 0;
+    let mut rusage: libc::rusage = unsafe { mem::zeroed() };
+    while unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) } < 0 {
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+    // `ru_maxrss` is in kilobytes
+    Ok((
+        ExitStatus::from_raw(status),
+        rusage.ru_maxrss as usize * 1024,
+    ))
+}
+
+/// Result of running a program in a limited cgroup.
+struct Execution {
+    exit_status: ExitStatus,
+    resource_usage: Resource,
+    /// Whether any process has been killed by the OOM killer.
+    oom_killed: bool,
+    limit: Resource,
+}
+
+impl Execution {
+    /// Get the report if the program failed to run within its limit.
+    ///
+    /// Return `None` if the program exit successfully within the limit.
+    fn failure(&self) -> Option<Report> {
+        if self.resource_usage.cpu_time > self.limit.cpu_time
+            || self.resource_usage.real_time >= self.limit.real_time
+        {
+            return Some(Report::TimeLimitExceeded);
+        }
+        // The memory limit of the cgroup is enforced by the OOM killer
+        if self.oom_killed {
+            return Some(Report::MemoryLimitExceeded);
+        }
+        if !self.exit_status.success() {
+            return Some(Report::RuntimeError);
+        }
+        None
+    }
+
+    /// Get the result of a testlib compatible checker or interactor
+    /// whose stderr is redirected to `message_file`.
+    ///
+    /// Return `None` if the program exceeded its limit.
+    fn check_result(&self, message_file: &Path) -> Result<Option<CheckResult>> {
+        match self.failure() {
+            Some(Report::TimeLimitExceeded) | Some(Report::MemoryLimitExceeded) => Ok(None),
+            _ => Ok(Some(CheckResult::from_testlib(
+                self.exit_status,
+                &fs::read(message_file)?,
+            ))),
+        }
+    }
+}
+
+/// Generate the report of a test case from the check result.
+fn report(result: &CheckResult, resource_usage: Resource) -> Report {
+    match result.verdict {
+        Verdict::Accepted => Report::Accepted { resource_usage },
+        Verdict::PresentationError => Report::PresentationError {
+            message: result.comment.clone(),
+        },
+        Verdict::PartiallyCorrect => Report::PartiallyCorrect {
+            score: result.score.unwrap_or(0.0),
+            message: result.comment.clone(),
+        },
+        Verdict::Fail => Report::SystemError,
+        _ => Report::WrongAnswer {
+            message: result.comment.clone(),
+        },
+    }
+}
+
+/// Check that `subtasks` are not empty and only refer to
+/// existing test cases and earlier subtasks.
+fn check_subtasks(subtasks: &[Subtask], case_count: usize) -> Result<()> {
+    for (i, subtask) in subtasks.iter().enumerate() {
+        if !subtask.points.is_finite() || subtask.points < 0.0 {
+            return Err(Error::Config(format!(
+                "Invalid points of subtask {}: {}",
+                i, subtask.points
+            )));
+        }
+        if subtask.cases.is_empty() {
+            return Err(Error::Config(format!("Subtask {} has no test case", i)));
+        }
+        if let Some(case) = subtask.cases.iter().find(|&&case| case >= case_count) {
+            return Err(Error::Config(format!(
+                "Subtask {} refers to test case {} but there are only {} test cases",
+                i, case, case_count
+            )));
+        }
+        if let Some(dependency) = subtask.dependencies.iter().find(|&&j| j >= i) {
+            return Err(Error::Config(format!(
+                "Subtask {} depends on subtask {} which is not before it",
+                i, dependency
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Compile `source` to `executable_file`.
+///
+/// Return `false` if the language is not supported or the compiling process failed.
+fn compile(source: &Source, executable_file: &Path) -> Result<bool> {
+    match Compiler::new(&source.language) {
+        Some(compiler) => Ok(compiler.compile(source, executable_file)?.status.success()),
+        None => Ok(false),
+    }
+}
+
+/// Create a temporary file with the given suffix and content.
+fn temp_file(suffix: &str, content: &[u8]) -> Result<TempPath> {
+    let mut res = tempfile::Builder::new().suffix(suffix).tempfile()?;
+    res.write_all(content)?;
+    Ok(res.into_temp_path())
+}
+
+/// Filesystem path of the content of a test case.
+///
+/// Inline content is written to a temporary file,
+/// while content stored in a file is used directly without copying.
+enum ContentFile<'c> {
+    File(&'c Path),
+    Temp(TempPath),
+}
+
+impl<'c> ContentFile<'c> {
+    fn new(content: &'c Content, suffix: &str) -> Result<ContentFile<'c>> {
+        match content {
+            Content::File(path) => Ok(ContentFile::File(path)),
+            Content::Text(text) => Ok(ContentFile::Temp(temp_file(suffix, text.as_bytes())?)),
+            Content::Bytes(bytes) => Ok(ContentFile::Temp(temp_file(suffix, bytes)?)),
+        }
+    }
+}
+
+impl<'c> AsRef<Path> for ContentFile<'c> {
+    fn as_ref(&self) -> &Path {
+        match self {
+            ContentFile::File(path) => path,
+            ContentFile::Temp(path) => path,
+        }
+    }
+}
+
+/// Build a cgroup context limited by `limit` with `builder`.
+///
+/// The cpu is not throttled, the time limits are enforced
+/// by killing the program when it is waited.
+fn limited_cgroup(builder: cgroup::Builder, limit: &Resource) -> Result<cgroup::Context> {
+    if limit.cpu_time == Duration::from_secs(0) || limit.real_time == Duration::from_secs(0) {
+        return Err(Error::Config(format!(
+            "Time limit must be positive: {:?}",
+            limit
+        )));
+    }
+
+    // Swap is counted in the memory limit so that it can not be escaped by swapping
+    let cg_ctx = builder
+        .memsw_limit(limit.memory)
+        .pids_controller(limit.processes.is_some())
+        .build()?;
+
+    if let Some(processes) = limit.processes {
+        cg_ctx
+            .pids_controller()
+            .ok_or_else(|| controller_disabled("pids"))?
+            .max()
+            .write(&processes)?;
+    }
+
+    Ok(cg_ctx)
+}
+
+/// Error of a cgroup controller which is required but not enabled.
+fn controller_disabled(name: &str) -> Error {
+    Error::Cgroup(format!("The {} controller is not enabled", name))
+}
+
+#[cfg(test)]
+mod tests;
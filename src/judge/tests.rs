@@ -0,0 +1,611 @@
+use super::*;
+
+use std::time::{Duration, Instant};
+
+const A_PLUS_B: &str = r#"
+#include<stdio.h>
+int main() {
+    int a, b;
+    scanf("%d%d", &a, &b);
+    printf("%d\n", a + b);
+    return 0;
+}
+"#;
+
+const A_MINUS_B: &str = r#"
+#include<stdio.h>
+int main() {
+    int a, b;
+    scanf("%d%d", &a, &b);
+    printf("%d\n", a - b);
+    return 0;
+}
+"#;
+
+const A_PLUS_B_WITH_SPACE: &str = r#"
+#include<stdio.h>
+int main() {
+    int a, b;
+    scanf("%d%d", &a, &b);
+    printf(" %d\n", a + b);
+    return 0;
+}
+"#;
+
+const INFINITE_LOOP: &str = r#"
+int main() {
+    volatile int i = 0;
+    for (;;) { i++; }
+    return 0;
+}
+"#;
+
+const BUSY_HALF_SECOND: &str = r#"
+#include<stdio.h>
+#include<time.h>
+int main() {
+    int a, b;
+    scanf("%d%d", &a, &b);
+    while (clock() < CLOCKS_PER_SEC / 2) {}
+    printf("%d\n", a + b);
+    return 0;
+}
+"#;
+
+const SLEEP_WITH_CHILD: &str = r#"
+#define _POSIX_C_SOURCE 200809L
+#include<unistd.h>
+int main() {
+    fork();
+    sleep(100);
+    return 0;
+}
+"#;
+
+const LARGE_MEMORY: &str = r#"
+#include<stdlib.h>
+int main() {
+    volatile char *buf = malloc(64 * 1024 * 1024);
+    for (int i = 0; i < 64 * 1024 * 1024; i += 4096) { buf[i] = 1; }
+    return 0;
+}
+"#;
+
+const READ_ALL: &str = r#"
+#include<stdio.h>
+int main() {
+    int a, b;
+    scanf("%d%d", &a, &b);
+    while (getchar() != EOF) {}
+    printf("%d\n", a + b);
+    return 0;
+}
+"#;
+
+const RUNTIME_ERROR: &str = r#"
+int main() {
+    return 1;
+}
+"#;
+
+const FORK_MANY: &str = r#"
+#define _POSIX_C_SOURCE 200809L
+#include<stdio.h>
+#include<unistd.h>
+int main() {
+    int a, b, i;
+    scanf("%d%d", &a, &b);
+    for (i = 0; i < 64; i++) {
+        pid_t pid = fork();
+        if (pid == 0) {
+            _exit(0);
+        } else if (pid < 0) {
+            printf("limited\n");
+            return 0;
+        }
+    }
+    printf("%d\n", a + b);
+    return 0;
+}
+"#;
+
+const COMPILE_ERROR: &str = r#"
+int main() {
+    return 0
+}
+"#;
+
+fn task(code: &str) -> Task {
+    Task {
+        source: Source {
+            language: String::from("c.gcc"),
+            code: String::from(code),
+        },
+        problem: Problem::Normal {
+            limit: Resource::new(
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                16 * 1024 * 1024,
+            ),
+            cases: vec![
+                TestCase {
+                    input: Content::from("1 2"),
+                    answer: Content::from("3"),
+                },
+                TestCase {
+                    input: Content::from("2 2"),
+                    answer: Content::from("4"),
+                },
+            ],
+            subtasks: Vec::new(),
+        },
+    }
+}
+
+#[test]
+fn test_accepted() -> io::Result<()> {
+    let judgement = Judge::new().judge(&task(A_PLUS_B))?;
+    assert_eq!(judgement.reports.len(), 2);
+    match judgement.verdict {
+        Report::Accepted { resource_usage } => {
+            assert!(resource_usage.real_time <= Duration::from_secs(2));
+            assert!(resource_usage.memory <= 16 * 1024 * 1024);
+        }
+        report => panic!("Unexpected report: {}", report),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_wrong_answer() -> io::Result<()> {
+    let judgement = Judge::new().judge(&task(A_MINUS_B))?;
+    match judgement.verdict {
+        Report::WrongAnswer { message } => {
+            assert_eq!(message, r#"Line 1, column 1: expected "3", found "-1""#)
+        }
+        report => panic!("Unexpected report: {}", report),
+    }
+    assert_eq!(judgement.reports.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_file_backed_cases() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let input_file = dir.path().join("1.in");
+    let answer_file = dir.path().join("1.ans");
+    fs::write(&input_file, "40 2")?;
+    fs::write(&answer_file, "42\n")?;
+
+    let mut task = task(A_PLUS_B);
+    if let Problem::Normal { cases, .. } = &mut task.problem {
+        cases.push(TestCase {
+            input: Content::from(input_file.as_path()),
+            answer: Content::from(answer_file),
+        });
+        cases.push(TestCase {
+            input: Content::from(b"5 5".to_vec()),
+            answer: Content::from(b"10".to_vec()),
+        });
+    }
+    let judgement = Judge::new().judge(&task)?;
+    assert_eq!(judgement.reports.len(), 4);
+    assert!(matches!(judgement.verdict, Report::Accepted { .. }));
+
+    task.problem = Problem::Normal {
+        limit: Resource::new(
+            Duration::from_secs(1),
+            Duration::from_secs(2),
+            16 * 1024 * 1024,
+        ),
+        cases: vec![TestCase {
+            input: Content::from(dir.path().join("missing.in")),
+            answer: Content::from("0"),
+        }],
+        subtasks: Vec::new(),
+    };
+    assert!(Judge::new().judge(&task).is_err());
+    Ok(())
+}
+
+fn subtask(points: f64, cases: Vec<usize>, policy: Policy, dependencies: Vec<usize>) -> Subtask {
+    Subtask {
+        points,
+        cases,
+        policy,
+        dependencies,
+    }
+}
+
+#[test]
+fn test_subtask_score() {
+    let accepted = Report::Accepted {
+        resource_usage: Resource::new(Duration::from_secs(0), Duration::from_secs(0), 0),
+    };
+    let partially_correct = Report::PartiallyCorrect {
+        score: 0.5,
+        message: String::new(),
+    };
+    let judgement = Judgement::from_reports(vec![
+        accepted.clone(),
+        partially_correct,
+        Report::WrongAnswer {
+            message: String::new(),
+        },
+        accepted,
+    ]);
+    assert_eq!(judgement.score, None);
+
+    let subtasks = vec![
+        subtask(10.0, vec![0, 1], Policy::Min, vec![]),
+        subtask(20.0, vec![0, 1], Policy::Sum, vec![]),
+        subtask(30.0, vec![0, 1], Policy::AllOrNothing, vec![]),
+        subtask(40.0, vec![3], Policy::Min, vec![]),
+        subtask(50.0, vec![3], Policy::Min, vec![0]),
+        subtask(60.0, vec![3], Policy::Min, vec![3]),
+        subtask(70.0, vec![2, 3], Policy::Sum, vec![3]),
+        subtask(80.0, vec![], Policy::AllOrNothing, vec![]),
+    ];
+    let score = judgement.with_subtasks(&subtasks).score.unwrap();
+    assert_eq!(
+        score.subtasks,
+        vec![5.0, 15.0, 0.0, 40.0, 0.0, 60.0, 35.0, 0.0]
+    );
+    assert_eq!(score.points, 155.0);
+
+    let judgement = Judgement::failed(Report::CompileError).with_subtasks(&subtasks);
+    assert_eq!(judgement.score.unwrap().points, 0.0);
+}
+
+#[test]
+fn test_subtasks() -> io::Result<()> {
+    let mut task = task(A_PLUS_B);
+    if let Problem::Normal {
+        cases, subtasks, ..
+    } = &mut task.problem
+    {
+        cases.push(TestCase {
+            input: Content::from("1 1"),
+            answer: Content::from("3"),
+        });
+        subtasks.push(subtask(30.0, vec![0], Policy::Min, vec![]));
+        subtasks.push(subtask(70.0, vec![0, 1, 2], Policy::Sum, vec![0]));
+    }
+    let judgement = Judge::new().judge(&task)?;
+    assert!(matches!(judgement.verdict, Report::WrongAnswer { .. }));
+    let score = judgement.score.unwrap();
+    assert_eq!(score.subtasks[0], 30.0);
+    assert!((score.subtasks[1] - 70.0 * 2.0 / 3.0).abs() < 1e-9);
+
+    let judgement = Judge::new().judge(&Task {
+        source: Source {
+            language: String::from("c.gcc"),
+            code: String::from(COMPILE_ERROR),
+        },
+        ..task.clone()
+    })?;
+    assert_eq!(judgement.verdict, Report::CompileError);
+    assert_eq!(judgement.score.unwrap().points, 0.0);
+
+    for invalid in [
+        subtask(10.0, vec![3], Policy::Min, vec![]),
+        subtask(10.0, vec![0], Policy::Min, vec![2]),
+        subtask(-1.0, vec![0], Policy::Min, vec![]),
+        subtask(10.0, vec![], Policy::AllOrNothing, vec![]),
+    ] {
+        let mut task = task.clone();
+        if let Problem::Normal { subtasks, .. } = &mut task.problem {
+            subtasks.push(invalid);
+        }
+        assert!(matches!(Judge::new().judge(&task), Err(Error::Config(_))));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_presentation_error() -> io::Result<()> {
+    let judgement = Judge::new().judge(&task(A_PLUS_B_WITH_SPACE))?;
+    assert!(matches!(
+        judgement.verdict,
+        Report::PresentationError { .. }
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_time_limit_exceeded() -> io::Result<()> {
+    let judgement = Judge::new().judge(&task(INFINITE_LOOP))?;
+    assert_eq!(judgement.verdict, Report::TimeLimitExceeded);
+    Ok(())
+}
+
+#[test]
+fn test_real_time_limit_exceeded() -> io::Result<()> {
+    let start_time = Instant::now();
+    let judgement = Judge::new().judge(&task(SLEEP_WITH_CHILD))?;
+    assert_eq!(judgement.verdict, Report::TimeLimitExceeded);
+    // Both processes are killed when the time limit is exceeded
+    assert!(start_time.elapsed() < Duration::from_secs(10));
+    Ok(())
+}
+
+#[test]
+fn test_cpu_not_throttled() -> io::Result<()> {
+    let mut task = task(BUSY_HALF_SECOND);
+    if let Problem::Normal { limit, .. } = &mut task.problem {
+        limit.real_time = Duration::from_secs(4);
+    }
+    let judgement = Judge::new().judge(&task)?;
+    match judgement.verdict {
+        Report::Accepted { resource_usage } => {
+            assert!(resource_usage.real_time < Duration::from_secs(2));
+        }
+        report => panic!("Unexpected report: {}", report),
+    }
+
+    task.source.code = String::from(INFINITE_LOOP);
+    if let Problem::Normal { limit, .. } = &mut task.problem {
+        limit.cpu_time = Duration::from_millis(10);
+        limit.real_time = Duration::from_secs(5);
+    }
+    let start_time = Instant::now();
+    let judgement = Judge::new().judge(&task)?;
+    assert_eq!(judgement.verdict, Report::TimeLimitExceeded);
+    assert!(start_time.elapsed() < Duration::from_secs(4));
+    Ok(())
+}
+
+#[test]
+fn test_memory_limit_exceeded() -> io::Result<()> {
+    let judgement = Judge::new().judge(&task(LARGE_MEMORY))?;
+    assert_eq!(judgement.verdict, Report::MemoryLimitExceeded);
+    Ok(())
+}
+
+#[test]
+fn test_large_input_memory() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let input_file = dir.path().join("large.in");
+    let mut input = b"1 2".to_vec();
+    input.resize(32 * 1024 * 1024, b' ');
+    fs::write(&input_file, input)?;
+
+    let mut task = task(READ_ALL);
+    if let Problem::Normal { cases, .. } = &mut task.problem {
+        cases.push(TestCase {
+            input: Content::from(input_file),
+            answer: Content::from("3"),
+        });
+    }
+    let judgement = Judge::new().judge(&task)?;
+    match judgement.reports.last() {
+        Some(Report::Accepted { resource_usage }) => {
+            assert!(resource_usage.memory < 4 * 1024 * 1024);
+        }
+        report => panic!("Unexpected report: {:?}", report),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_runtime_error() -> io::Result<()> {
+    let judgement = Judge::new().judge(&task(RUNTIME_ERROR))?;
+    assert_eq!(judgement.verdict, Report::RuntimeError);
+    Ok(())
+}
+
+#[test]
+fn test_process_limit() -> io::Result<()> {
+    let judgement = Judge::new().judge(&task(FORK_MANY))?;
+    assert!(matches!(judgement.verdict, Report::Accepted { .. }));
+
+    let mut task = task(FORK_MANY);
+    if let Problem::Normal { limit, cases, .. } = &mut task.problem {
+        *limit = limit.processes(8);
+        // Exited children are counted until they are reaped
+        for case in cases.iter_mut() {
+            case.answer = Content::from("limited");
+        }
+    }
+    let judgement = Judge::new().judge(&task)?;
+    assert!(matches!(judgement.verdict, Report::Accepted { .. }));
+    Ok(())
+}
+
+#[test]
+fn test_cgroup_parent() -> io::Result<()> {
+    let parent = cgroup::Builder::new().build()?;
+    let judgement = Judge::new().cgroup_parent(&parent).judge(&task(A_PLUS_B))?;
+    assert!(matches!(judgement.verdict, Report::Accepted { .. }));
+    Ok(())
+}
+
+#[test]
+fn test_invalid_limit() {
+    let mut task = task(A_PLUS_B);
+    if let Problem::Normal { limit, .. } = &mut task.problem {
+        limit.real_time = Duration::from_secs(0);
+    }
+    match Judge::new().judge(&task) {
+        Err(Error::Config(_)) => {}
+        judgement => panic!("Unexpected judgement: {:?}", judgement),
+    }
+}
+
+#[test]
+fn test_compile_error() -> io::Result<()> {
+    let judgement = Judge::new().judge(&task(COMPILE_ERROR))?;
+    assert_eq!(judgement.verdict, Report::CompileError);
+    assert!(judgement.reports.is_empty());
+    Ok(())
+}
+
+const GREATER_THAN_INPUT: &str = r#"
+#include<stdio.h>
+int main(int argc, char *argv[]) {
+    FILE *input = fopen(argv[1], "r");
+    FILE *output = fopen(argv[2], "r");
+    int a, b, c;
+    if (fscanf(input, "%d%d", &a, &b) != 2) { return 2; }
+    if (fscanf(output, "%d", &c) != 1) { return 1; }
+    return c > a + b ? 0 : 1;
+}
+"#;
+
+const PLUS_ONE: &str = r#"
+#include<stdio.h>
+int main() {
+    int a, b;
+    scanf("%d%d", &a, &b);
+    printf("%d\n", a + b + 1);
+    return 0;
+}
+"#;
+
+fn special_task(code: &str, spj: &str) -> Task {
+    let mut task = task(code);
+    if let Problem::Normal {
+        limit,
+        cases,
+        subtasks,
+    } = task.problem
+    {
+        task.problem = Problem::Special {
+            limit,
+            cases,
+            subtasks,
+            spj: Source {
+                language: String::from("c.gcc"),
+                code: String::from(spj),
+            },
+        };
+    }
+    task
+}
+
+#[test]
+fn test_special_judge() -> io::Result<()> {
+    let judge = Judge::new();
+
+    let judgement = judge.judge(&special_task(PLUS_ONE, GREATER_THAN_INPUT))?;
+    match judgement.verdict {
+        Report::Accepted { .. } => {}
+        report => panic!("Unexpected report: {}", report),
+    }
+
+    let judgement = judge.judge(&special_task(A_PLUS_B, GREATER_THAN_INPUT))?;
+    assert!(matches!(judgement.verdict, Report::WrongAnswer { .. }));
+    assert_eq!(judge.program_cache.lock().unwrap().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_special_judge_compile_error() -> io::Result<()> {
+    let judgement = Judge::new().judge(&special_task(A_PLUS_B, COMPILE_ERROR))?;
+    assert_eq!(judgement.verdict, Report::SystemError);
+    Ok(())
+}
+
+const GUESS_NUMBER_INTERACTOR: &str = r#"
+#include<stdio.h>
+int main(int argc, char *argv[]) {
+    FILE *input = fopen(argv[1], "r");
+    FILE *output = fopen(argv[2], "w");
+    int n, guess, queries = 0;
+    if (fscanf(input, "%d", &n) != 1) { return 2; }
+    while (scanf("%d", &guess) == 1) {
+        if (++queries > 10) { return 1; }
+        if (guess == n) {
+            printf("=\n");
+            fflush(stdout);
+            fprintf(output, "%d\n", guess);
+            return 0;
+        }
+        printf(guess > n ? "<\n" : ">\n");
+        fflush(stdout);
+    }
+    return 1;
+}
+"#;
+
+const BINARY_SEARCH: &str = r#"
+#include<stdio.h>
+int main() {
+    int low = 1, high = 100;
+    char reply[4];
+    while (low <= high) {
+        int mid = (low + high) / 2;
+        printf("%d\n", mid);
+        fflush(stdout);
+        if (scanf("%3s", reply) != 1) { return 1; }
+        if (reply[0] == '=') { return 0; }
+        if (reply[0] == '<') { high = mid - 1; } else { low = mid + 1; }
+    }
+    return 0;
+}
+"#;
+
+const LINEAR_SEARCH: &str = r#"
+#include<stdio.h>
+int main() {
+    char reply[4];
+    for (int i = 1; i <= 100; i++) {
+        printf("%d\n", i);
+        fflush(stdout);
+        if (scanf("%3s", reply) != 1) { return 1; }
+        if (reply[0] == '=') { return 0; }
+    }
+    return 0;
+}
+"#;
+
+fn interactive_task(code: &str) -> Task {
+    Task {
+        source: Source {
+            language: String::from("c.gcc"),
+            code: String::from(code),
+        },
+        problem: Problem::Interactive {
+            limit: Resource::new(
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                16 * 1024 * 1024,
+            ),
+            cases: vec![
+                TestCase {
+                    input: Content::from("42"),
+                    answer: Content::from("42"),
+                },
+                TestCase {
+                    input: Content::from("99"),
+                    answer: Content::from("99"),
+                },
+            ],
+            interactor: Source {
+                language: String::from("c.gcc"),
+                code: String::from(GUESS_NUMBER_INTERACTOR),
+            },
+            subtasks: Vec::new(),
+        },
+    }
+}
+
+#[test]
+fn test_interactive() -> io::Result<()> {
+    let judge = Judge::new();
+
+    let judgement = judge.judge(&interactive_task(BINARY_SEARCH))?;
+    match judgement.verdict {
+        Report::Accepted { .. } => {}
+        report => panic!("Unexpected report: {}", report),
+    }
+
+    let judgement = judge.judge(&interactive_task(LINEAR_SEARCH))?;
+    assert!(matches!(judgement.verdict, Report::WrongAnswer { .. }));
+
+    let judgement = judge.judge(&interactive_task(INFINITE_LOOP))?;
+    assert_eq!(judgement.verdict, Report::TimeLimitExceeded);
+
+    Ok(())
+}
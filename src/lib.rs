@@ -4,11 +4,17 @@
 pub mod checker;
 /// A simple API for different compilers.
 pub mod compiler;
+/// Error type of the crate.
+pub mod error;
 /// Executor for running a single program with resource limit and system calls filter.
 pub mod executor;
+/// Judge pipeline from a task to the reports of its test cases.
+pub mod judge;
 /// Structures definitions.
 pub mod structures;
 
 pub use checker::Checker;
 pub use compiler::Compiler;
+pub use error::{Error, Result};
+pub use judge::Judge;
 pub use structures::*;
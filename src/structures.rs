@@ -1,25 +1,79 @@
 //! Structures for online judge system.
+//!
+//! All structures can be serialized and deserialized by serde,
+//! so they can be exchanged in JSON or bincode.
+//! Field names are kept as they are in Rust and enum variants are
+//! externally tagged with snake case names. Durations are serialized
+//! as `{"secs": u64, "nanos": u32}` and memory is in bytes.
+//! The limit of `processes` is optional.
+//! The `policy` and `dependencies` of a subtask are optional,
+//! which default to `min` and no dependency.
+//!
+//! A task in JSON looks like:
+//!
+//! ```json
+//! {
+//!     "source": { "language": "c.gcc", "code": "int main() { return 0; }" },
+//!     "problem": {
+//!         "normal": {
+//!             "limit": {
+//!                 "cpu_time": { "secs": 1, "nanos": 0 },
+//!                 "real_time": { "secs": 2, "nanos": 0 },
+//!                 "memory": 16777216
+//!             },
+//!             "cases": [
+//!                 { "input": { "text": "1 2" }, "answer": { "text": "3" } },
+//!                 { "input": { "file": "/data/2.in" }, "answer": { "file": "/data/2.ans" } }
+//!             ],
+//!             "subtasks": [
+//!                 { "points": 40.0, "cases": [0] },
+//!                 { "points": 60.0, "cases": [0, 1], "policy": "sum", "dependencies": [0] }
+//!             ]
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! And a report looks like `{"wrong_answer": {"message": "..."}}`
+//! or `"time_limit_exceeded"`.
 use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+
 /// Basic judge task.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     pub source: Source,
     pub problem: Problem,
 }
 
 /// Definition of some common problem types.
-#[derive(Clone, Debug, PartialEq)]
+///
+/// A problem without subtasks is judged as pass or fail only.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Problem {
     Normal {
         limit: Resource,
         cases: Vec<TestCase>,
+        #[serde(default)]
+        subtasks: Vec<Subtask>,
     },
     Special {
         limit: Resource,
         cases: Vec<TestCase>,
         spj: Source,
+        #[serde(default)]
+        subtasks: Vec<Subtask>,
+    },
+    Interactive {
+        limit: Resource,
+        cases: Vec<TestCase>,
+        interactor: Source,
+        #[serde(default)]
+        subtasks: Vec<Subtask>,
     },
 }
 
@@ -30,6 +84,7 @@ impl Problem {
         match self {
             Normal { cases, .. } => cases.len(),
             Special { cases, .. } => cases.len(),
+            Interactive { cases, .. } => cases.len(),
         }
     }
 
@@ -37,31 +92,136 @@ impl Problem {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Return the subtasks of this problem.
+    pub fn subtasks(&self) -> &[Subtask] {
+        use Problem::*;
+        match self {
+            Normal { subtasks, .. } => subtasks,
+            Special { subtasks, .. } => subtasks,
+            Interactive { subtasks, .. } => subtasks,
+        }
+    }
+}
+
+/// A group of test cases worth some points.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Subtask {
+    /// Full points of the subtask.
+    pub points: f64,
+    /// Indices of the test cases in the problem.
+    ///
+    /// A test case can be shared by several subtasks.
+    pub cases: Vec<usize>,
+    #[serde(default)]
+    pub policy: Policy,
+    /// Indices of the subtasks which must get full points
+    /// before this subtask gets any point.
+    ///
+    /// Only earlier subtasks can be depended on.
+    #[serde(default)]
+    pub dependencies: Vec<usize>,
+}
+
+/// The way to score a subtask from the scores of its test cases.
+///
+/// The score of a test case is 1 if it is accepted,
+/// the score given by the checker if it is partially correct, and 0 otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Policy {
+    /// Full points scaled by the minimum score of all test cases.
+    #[default]
+    Min,
+    /// Full points are divided equally among all test cases.
+    Sum,
+    /// Full points only if all test cases are accepted.
+    AllOrNothing,
 }
 
 /// Basic test case.
 ///
 /// Only include a input content and a answer content.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TestCase {
-    pub input: String,
-    pub answer: String,
+    pub input: Content,
+    pub answer: Content,
+}
+
+/// Content of the input or the answer of a test case.
+///
+/// Large or binary data should be stored in a file,
+/// which is read only when it is used.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Content {
+    /// Inline text.
+    Text(String),
+    /// Inline binary data.
+    Bytes(Vec<u8>),
+    /// Data stored in the file of the path.
+    File(PathBuf),
+}
+
+impl From<&str> for Content {
+    fn from(text: &str) -> Content {
+        Content::Text(text.to_owned())
+    }
+}
+
+impl From<String> for Content {
+    fn from(text: String) -> Content {
+        Content::Text(text)
+    }
+}
+
+impl From<Vec<u8>> for Content {
+    fn from(bytes: Vec<u8>) -> Content {
+        Content::Bytes(bytes)
+    }
+}
+
+impl From<&Path> for Content {
+    fn from(path: &Path) -> Content {
+        Content::File(path.to_owned())
+    }
+}
+
+impl From<PathBuf> for Content {
+    fn from(path: PathBuf) -> Content {
+        Content::File(path)
+    }
 }
 
 /// Basic source with the language and code.
 ///
 /// The language is usually formatted into "{suffix}.{compiler}"
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Source {
     pub language: String,
     pub code: String,
 }
 
 /// Definition of all kinds of judge report.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Report {
-    Accepted { resource_usage: Resource },
-    WrongAnswer,
+    Accepted {
+        resource_usage: Resource,
+    },
+    /// The output is wrong and the message tells why.
+    WrongAnswer {
+        message: String,
+    },
+    /// The output is correct except for the white spaces.
+    PresentationError {
+        message: String,
+    },
+    /// The output is partially correct and gets a score in `[0, 1]`.
+    PartiallyCorrect {
+        score: f64,
+        message: String,
+    },
     TimeLimitExceeded,
     MemoryLimitExceeded,
     RuntimeError,
@@ -69,6 +229,17 @@ pub enum Report {
     SystemError,
 }
 
+impl Report {
+    /// Return the score of a test case with this report, which is in `[0, 1]`.
+    pub fn score(&self) -> f64 {
+        match self {
+            Report::Accepted { .. } => 1.0,
+            Report::PartiallyCorrect { score, .. } => *score,
+            _ => 0.0,
+        }
+    }
+}
+
 impl Display for Report {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -76,8 +247,17 @@ impl Display for Report {
                 writeln!(f, "Accepted:")?;
                 writeln!(f, "{}", resource_usage)?;
             }
-            Report::WrongAnswer => {
-                writeln!(f, "Wrong Answer")?;
+            Report::WrongAnswer { message } => {
+                writeln!(f, "Wrong Answer:")?;
+                writeln!(f, "{}", message)?;
+            }
+            Report::PresentationError { message } => {
+                writeln!(f, "Presentation Error:")?;
+                writeln!(f, "{}", message)?;
+            }
+            Report::PartiallyCorrect { score, message } => {
+                writeln!(f, "Partially Correct: {}", score)?;
+                writeln!(f, "{}", message)?;
             }
             Report::TimeLimitExceeded => {
                 writeln!(f, "Time Limit Exceeded")?;
@@ -100,11 +280,16 @@ impl Display for Report {
 }
 
 /// Definition of resource.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Resource {
     pub cpu_time: Duration,
     pub real_time: Duration,
     pub memory: usize, // in bytes
+    /// Maximum number of processes and threads running at the same time.
+    ///
+    /// Only used as a limit, `None` means no limit.
+    #[serde(default)]
+    pub processes: Option<usize>,
 }
 
 impl Resource {
@@ -113,8 +298,15 @@ impl Resource {
             real_time,
             cpu_time,
             memory,
+            processes: None,
         }
     }
+
+    /// Limit the number of processes and threads.
+    pub fn processes(mut self, processes: usize) -> Resource {
+        self.processes = Some(processes);
+        self
+    }
 }
 
 impl Display for Resource {
@@ -122,6 +314,116 @@ impl Display for Resource {
         writeln!(f, "Real Time: {}", self.real_time.as_nanos())?;
         writeln!(f, "CPU Time: {}", self.cpu_time.as_nanos())?;
         writeln!(f, "Memory: {}", self.memory)?;
+        if let Some(processes) = self.processes {
+            writeln!(f, "Processes: {}", processes)?;
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bincode;
+    use serde_json;
+
+    fn task() -> Task {
+        Task {
+            source: Source {
+                language: String::from("c.gcc"),
+                code: String::from("int main() { return 0; }"),
+            },
+            problem: Problem::Normal {
+                limit: Resource::new(
+                    Duration::from_secs(1),
+                    Duration::from_secs(2),
+                    16 * 1024 * 1024,
+                ),
+                cases: vec![
+                    TestCase {
+                        input: Content::from("1 2"),
+                        answer: Content::from("3"),
+                    },
+                    TestCase {
+                        input: Content::from(Path::new("/data/2.in")),
+                        answer: Content::from(b"4".to_vec()),
+                    },
+                ],
+                subtasks: vec![
+                    Subtask {
+                        points: 40.0,
+                        cases: vec![0],
+                        policy: Policy::Min,
+                        dependencies: Vec::new(),
+                    },
+                    Subtask {
+                        points: 60.0,
+                        cases: vec![0, 1],
+                        policy: Policy::AllOrNothing,
+                        dependencies: vec![0],
+                    },
+                ],
+            },
+        }
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let json = r#"{
+            "source": { "language": "c.gcc", "code": "int main() { return 0; }" },
+            "problem": {
+                "normal": {
+                    "limit": {
+                        "cpu_time": { "secs": 1, "nanos": 0 },
+                        "real_time": { "secs": 2, "nanos": 0 },
+                        "memory": 16777216
+                    },
+                    "cases": [
+                        { "input": { "text": "1 2" }, "answer": { "text": "3" } },
+                        { "input": { "file": "/data/2.in" }, "answer": { "bytes": [52] } }
+                    ],
+                    "subtasks": [
+                        { "points": 40.0, "cases": [0] },
+                        {
+                            "points": 60.0,
+                            "cases": [0, 1],
+                            "policy": "all_or_nothing",
+                            "dependencies": [0]
+                        }
+                    ]
+                }
+            }
+        }"#;
+        assert_eq!(serde_json::from_str::<Task>(json).unwrap(), task());
+
+        let report = Report::WrongAnswer {
+            message: String::from("wrong"),
+        };
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"wrong_answer":{"message":"wrong"}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Report::TimeLimitExceeded).unwrap(),
+            r#""time_limit_exceeded""#
+        );
+    }
+
+    #[test]
+    fn test_bincode() {
+        let task = task();
+        let bytes = bincode::serialize(&task).unwrap();
+        assert_eq!(bincode::deserialize::<Task>(&bytes).unwrap(), task);
+
+        let report = Report::Accepted {
+            resource_usage: Resource::new(
+                Duration::from_millis(10),
+                Duration::from_millis(20),
+                1024,
+            ),
+        };
+        let bytes = bincode::serialize(&report).unwrap();
+        assert_eq!(bincode::deserialize::<Report>(&bytes).unwrap(), report);
+    }
+}